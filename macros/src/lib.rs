@@ -0,0 +1,216 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_macro_input, Ident, LitByteStr, LitStr, Token};
+
+/// The most steps the output of a program is computed for before giving up.
+const MAX_STEPS: u64 = 10_000_000;
+
+/// Checks Brainfuck code when compiling, expanding to a
+/// `headaches::Program` built without parsing it again.
+///
+/// Mismatched brackets are reported as compile errors. Give `output:`
+/// before the code to expand to the bytes the program writes instead, as a
+/// `&'static [u8; N]`, which fails to compile if the program reads input
+/// or runs for too long, such as `brainfuck!(output: "++++++++[>++++++++<-]>+.")`.
+#[proc_macro]
+pub fn brainfuck(input: TokenStream) -> TokenStream {
+    let Input { output, code } = parse_macro_input!(input as Input);
+    let source = code.value();
+    let parsed = match parse(&source) {
+        Ok(parsed) => parsed,
+        Err(message) => return error(&code, message),
+    };
+    if output {
+        return match run(&parsed.ops) {
+            Ok(bytes) => {
+                let bytes = LitByteStr::new(&bytes, code.span());
+                quote!(#bytes).into()
+            }
+            Err(message) => error(&code, message),
+        };
+    }
+    let ops = parsed.ops.iter().map(|op| match *op {
+        Op::Increment => quote!(::headaches::Op::Increment),
+        Op::Decrement => quote!(::headaches::Op::Decrement),
+        Op::Forward => quote!(::headaches::Op::Forward),
+        Op::Backward => quote!(::headaches::Op::Backward),
+        Op::Open(close) => quote!(::headaches::Op::Open(#close)),
+        Op::Close(open) => quote!(::headaches::Op::Close(#open)),
+        Op::Out => quote!(::headaches::Op::Out),
+        Op::In => quote!(::headaches::Op::In),
+    });
+    let spans = parsed.spans.iter().map(|span| {
+        let Span {
+            start,
+            end,
+            line,
+            column,
+        } = *span;
+        quote!(::headaches::Span { start: #start, end: #end, line: #line, column: #column })
+    });
+    quote!({
+        static OPS: &[::headaches::Op] = &[#(#ops),*];
+        static SPANS: &[::headaches::Span] = &[#(#spans),*];
+        ::headaches::Program::__from_parts(#code, OPS, SPANS)
+    })
+    .into()
+}
+
+/// The arguments of [`brainfuck!`]: the code, and whether to expand to
+/// its output.
+struct Input {
+    output: bool,
+    code: LitStr,
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let output = input.peek(Ident);
+        if output {
+            let ident: Ident = input.parse()?;
+            if ident != "output" {
+                return Err(syn::Error::new(ident.span(), "expected `output:` or code"));
+            }
+            input.parse::<Token![:]>()?;
+        }
+        let code = input.parse()?;
+        Ok(Self { output, code })
+    }
+}
+
+fn error(code: &LitStr, message: String) -> TokenStream {
+    let error: TokenStream2 = syn::Error::new(code.span(), message).to_compile_error();
+    error.into()
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Increment,
+    Decrement,
+    Forward,
+    Backward,
+    Open(usize),
+    Close(usize),
+    Out,
+    In,
+}
+
+#[derive(Clone, Copy)]
+struct Span {
+    start: usize,
+    end: usize,
+    line: usize,
+    column: usize,
+}
+
+struct Parsed {
+    ops: Vec<Op>,
+    spans: Vec<Span>,
+}
+
+/// Parses code the same way as `headaches::Program::parse`, returning the
+/// message of its error if it fails.
+fn parse(source: &str) -> Result<Parsed, String> {
+    let mut parsed = Parsed {
+        ops: vec![],
+        spans: vec![],
+    };
+    let mut opened = vec![];
+    let (mut line, mut column, mut shebang) = (0, 0, false);
+    for (offset, b) in source.bytes().enumerate() {
+        let span = Span {
+            start: offset,
+            end: offset + 1,
+            line: line + 1,
+            column: column + 1,
+        };
+        shebang = match offset {
+            0 => b == b'#',
+            1 => shebang && b == b'!',
+            _ => shebang && b != b'\n',
+        };
+        match b {
+            b'\n' => {
+                line += 1;
+                column = 0;
+            }
+            // The rest of a multi-byte character takes up no column.
+            _ if b & 0xC0 == 0x80 => {}
+            _ => column += 1,
+        }
+        if shebang {
+            continue;
+        }
+        let op = match b {
+            b'+' => Op::Increment,
+            b'-' => Op::Decrement,
+            b'>' => Op::Forward,
+            b'<' => Op::Backward,
+            b'[' => {
+                opened.push(parsed.ops.len());
+                // Patched once the matching `]` is found.
+                Op::Open(0)
+            }
+            b']' => match opened.pop() {
+                Some(open) => {
+                    parsed.ops[open] = Op::Open(parsed.ops.len());
+                    Op::Close(open)
+                }
+                None => {
+                    return Err(format!(
+                        "{}:{}: `]` has no matching `[`.",
+                        span.line, span.column
+                    ))
+                }
+            },
+            b'.' => Op::Out,
+            b',' => Op::In,
+            _ => continue,
+        };
+        parsed.ops.push(op);
+        parsed.spans.push(span);
+    }
+    match opened.pop() {
+        Some(open) => {
+            let span = parsed.spans[open];
+            Err(format!(
+                "{}:{}: `[` is never closed.",
+                span.line, span.column
+            ))
+        }
+        None => Ok(parsed),
+    }
+}
+
+/// Runs a program which reads no input, returning what it writes.
+fn run(ops: &[Op]) -> Result<Vec<u8>, String> {
+    let (mut mem, mut pointer, mut position) = (vec![0u8], 0, 0);
+    let mut output = vec![];
+    let mut steps = 0;
+    while let Some(&op) = ops.get(position) {
+        if steps == MAX_STEPS {
+            return Err(format!("the program ran for more than {MAX_STEPS} steps."));
+        }
+        steps += 1;
+        match op {
+            Op::Increment => mem[pointer] = mem[pointer].wrapping_add(1),
+            Op::Decrement => mem[pointer] = mem[pointer].wrapping_sub(1),
+            Op::Forward => {
+                pointer += 1;
+                if pointer == mem.len() {
+                    mem.push(0);
+                }
+            }
+            Op::Backward => pointer = pointer.saturating_sub(1),
+            Op::Open(close) if mem[pointer] == 0 => position = close,
+            Op::Close(open) if mem[pointer] != 0 => position = open,
+            Op::Open(_) | Op::Close(_) => {}
+            Op::Out => output.push(mem[pointer]),
+            Op::In => return Err("the program reads input, so its output is not known.".into()),
+        }
+        position += 1;
+    }
+    Ok(output)
+}
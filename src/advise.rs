@@ -0,0 +1,189 @@
+use alloc::rc::Rc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::cmp::Reverse;
+use core::fmt;
+use core::ops::Range;
+
+use crate::optimize::{stops_folding, FOLD_STEPS};
+use crate::{
+    Config, Diagnostic, Event, Hook, Interpreter, Io, IoError, Lint, LoopKind, Op, Program,
+    RuntimeError, Span, State,
+};
+
+/// How many times a loop runs before [`advise`] reports it.
+pub const HOT_LOOP: u64 = 1000;
+
+/// A suggestion for making a program faster or smaller, found by [`advise`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Advice {
+    /// A loop which ran many times.
+    HotLoop {
+        /// Where the loop is.
+        span: Span,
+        /// How many times its `]` ran.
+        iterations: u64,
+        /// What the loop does.
+        kind: LoopKind,
+        /// Whether optimizing replaces the loop with a single step.
+        lowered: bool,
+    },
+    /// Cells the pointer moved over but whose value was never read by a
+    /// `[`, `]`, or `.`.
+    UnreadCells(Range<usize>),
+    /// Code which can never execute.
+    DeadCode(Diagnostic),
+    /// The program reads no input, switches no tapes, makes no random bytes,
+    /// and halted quickly enough to be run ahead of time when fully
+    /// optimized.
+    Foldable {
+        /// How many steps it took.
+        steps: u64,
+    },
+}
+
+impl fmt::Display for Advice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Advice::HotLoop {
+                span,
+                iterations,
+                lowered: false,
+                ..
+            } => write!(
+                f,
+                "{span}: this loop ran {iterations} times, and is not one optimizing can replace."
+            ),
+            Advice::HotLoop {
+                span,
+                iterations,
+                kind: LoopKind::Other,
+                ..
+            } => write!(
+                f,
+                "{span}: this loop ran {iterations} times, which optimizing runs in one step."
+            ),
+            Advice::HotLoop {
+                span,
+                iterations,
+                kind,
+                ..
+            } => write!(
+                f,
+                "{span}: this loop ran {iterations} times, and is a {kind}, which optimizing runs in one step."
+            ),
+            Advice::UnreadCells(cells) if cells.len() == 1 => {
+                write!(f, "cell {} is never read.", cells.start)
+            }
+            Advice::UnreadCells(cells) => {
+                write!(f, "cells {} to {} are never read.", cells.start, cells.end - 1)
+            }
+            Advice::DeadCode(diagnostic) => {
+                write!(f, "{diagnostic} Run with --dead-code or -O to remove it.")
+            }
+            Advice::Foldable { steps } => write!(
+                f,
+                "the program halts after {steps} steps without reading input, switching tapes, or making random bytes, so fully optimizing runs it ahead of time."
+            ),
+        }
+    }
+}
+
+/// Remembers which cells had their value read.
+struct ReadCells(Vec<bool>);
+
+impl Hook for ReadCells {
+    fn on_event(&mut self, event: &Event, state: &State) -> Result<(), IoError> {
+        if let Event::Step {
+            op: Op::Open(_) | Op::Close(_) | Op::Out,
+            ..
+        } = event
+        {
+            if state.pointer >= self.0.len() {
+                self.0.resize(state.pointer + 1, false);
+            }
+            self.0[state.pointer] = true;
+        }
+        Ok(())
+    }
+}
+
+/// Runs a program as chosen by `config`, watching what it does, and
+/// suggests how it could be made faster or smaller.
+///
+/// Hot loops are listed first, from the one which ran the most, followed
+/// by unread cells, dead code, and whether the program can be run ahead
+/// of time. The result says whether the program halted, as a program
+/// which fails is only advised on as far as it got.
+pub fn advise(
+    program: &Program,
+    io: impl Io,
+    config: &Config,
+) -> (Vec<Advice>, Result<(), RuntimeError>) {
+    let read = Rc::new(RefCell::new(ReadCells(vec![])));
+    let mut interpreter = Interpreter::builder()
+        .io(io)
+        .config(config)
+        .coverage()
+        .hook(Rc::clone(&read))
+        .build(program.clone());
+    let result = interpreter.run();
+    let hits: Vec<u64> = interpreter
+        .coverage()
+        .expect("coverage is tracked")
+        .iter()
+        .map(|(_, hits)| hits)
+        .collect();
+
+    let mut hot: Vec<(usize, usize)> = program
+        .ops()
+        .iter()
+        .enumerate()
+        .filter_map(|(open, &op)| match op {
+            Op::Open(close) if hits[close] >= HOT_LOOP => Some((open, close)),
+            _ => None,
+        })
+        .collect();
+    hot.sort_by_key(|&(_, close)| Reverse(hits[close]));
+    let mut advice: Vec<Advice> = hot
+        .into_iter()
+        .map(|(open, close)| Advice::HotLoop {
+            span: program.spans()[open].to(program.spans()[close]),
+            iterations: hits[close],
+            kind: program.loop_kind(open).expect("a loop starts here"),
+            lowered: program.lower_loop(open).is_some(),
+        })
+        .collect();
+
+    let read = &read.borrow().0;
+    let used = interpreter.state().mem.len();
+    let mut cell = 0;
+    while cell < used {
+        if read.get(cell).copied().unwrap_or(false) {
+            cell += 1;
+            continue;
+        }
+        let start = cell;
+        while cell < used && !read.get(cell).copied().unwrap_or(false) {
+            cell += 1;
+        }
+        advice.push(Advice::UnreadCells(start..cell));
+    }
+
+    let (_, removed) = program.eliminate_dead_code();
+    let first = program.spans().first().map(|span| span.start);
+    advice.extend(
+        removed
+            .into_iter()
+            // A leading loop is never run, so it usually holds a comment.
+            .filter(|d| !(d.lint == Lint::DeadLoop && Some(d.span.start) == first))
+            .map(Advice::DeadCode),
+    );
+
+    let steps = interpreter.steps();
+    if result.is_ok() && steps <= FOLD_STEPS && !program.ops().iter().any(stops_folding) {
+        advice.push(Advice::Foldable { steps });
+    }
+    (advice, result)
+}
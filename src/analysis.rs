@@ -0,0 +1,322 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem;
+
+use crate::{Interpreter, Io, IoError, Op, Program, RuntimeError, State};
+
+/// Whether every run of the body of the loop at `open` provably returns to
+/// the cell the loop checks without changing it, so once entered the loop
+/// can never end.
+///
+/// Bodies which move left of that cell are never counted, as the pointer
+/// may stop at the first cell instead.
+pub(crate) fn keeps_guard(ops: &[Op], open: usize) -> bool {
+    let Op::Open(close) = ops[open] else {
+        return false;
+    };
+    untouched(ops, open + 1, close, 0) == Some(0)
+}
+
+/// Follows the pointer through `ops[start..end]` from `offset`, returning
+/// where it ends, or `None` if the cell at offset 0 may be changed or the
+/// pointer may move left of it.
+fn untouched(ops: &[Op], start: usize, end: usize, mut offset: usize) -> Option<usize> {
+    let mut i = start;
+    while i < end {
+        match ops[i] {
+            Op::Forward => offset += 1,
+            Op::Backward => offset = offset.checked_sub(1)?,
+            Op::Increment | Op::Decrement | Op::In | Op::Random if offset == 0 => return None,
+            Op::PrevTape | Op::NextTape => return None,
+            Op::Open(close) => {
+                // However many times the inner loop runs, it must leave the
+                // pointer where it found it.
+                if untouched(ops, i + 1, close, offset)? != offset {
+                    return None;
+                }
+                i = close;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    Some(offset)
+}
+
+/// The inputs [`equivalent`] runs both programs over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExhaustInputs {
+    /// The longest input tried.
+    pub len: usize,
+    /// The number of steps after which a run is stopped.
+    pub steps: u64,
+}
+
+/// What a program did with one input.
+#[derive(Debug)]
+pub struct Behavior {
+    /// The bytes it wrote.
+    pub output: Vec<u8>,
+    /// Whether it halted, or why it failed.
+    pub result: Result<(), RuntimeError>,
+}
+
+/// An input two programs behave differently on, found by [`equivalent`].
+#[derive(Debug)]
+pub struct Divergence {
+    /// The input given to both programs.
+    pub input: Vec<u8>,
+    /// What the first program did.
+    pub left: Behavior,
+    /// What the second program did.
+    pub right: Behavior,
+}
+
+/// Reads a fixed input, remembering whether a program asked for more.
+struct Exhaust<'a> {
+    input: &'a [u8],
+    read: usize,
+    past_end: bool,
+    output: Vec<u8>,
+}
+
+impl Io for Exhaust<'_> {
+    fn read(&mut self) -> Result<Option<u8>, IoError> {
+        let b = self.input.get(self.read).copied();
+        self.read += 1;
+        self.past_end |= b.is_none();
+        Ok(b)
+    }
+    fn write(&mut self, byte: u8) -> Result<(), IoError> {
+        self.output.push(byte);
+        Ok(())
+    }
+}
+
+/// Runs two programs over every input up to a length, returning the first
+/// one they write different output on or end differently on.
+///
+/// Inputs are tried in order, from each byte 0 to 255, and are only made
+/// longer while a program reads all of them, so there may be as many as
+/// 256 times more runs for each byte of `len`. Programs stopped after
+/// `inputs.steps` steps only differ if what they wrote so far does.
+pub fn equivalent(left: &Program, right: &Program, inputs: ExhaustInputs) -> Option<Divergence> {
+    let mut input = vec![];
+    explore(left, right, inputs, &mut input)
+}
+
+fn explore(
+    left: &Program,
+    right: &Program,
+    inputs: ExhaustInputs,
+    input: &mut Vec<u8>,
+) -> Option<Divergence> {
+    let (l, l_more) = behave(left, input, inputs.steps);
+    let (r, r_more) = behave(right, input, inputs.steps);
+    if diverges(&l, &r) {
+        return Some(Divergence {
+            input: input.clone(),
+            left: l,
+            right: r,
+        });
+    }
+    if input.len() < inputs.len && (l_more || r_more) {
+        for b in 0..=u8::MAX {
+            input.push(b);
+            let divergence = explore(left, right, inputs, input);
+            input.pop();
+            if divergence.is_some() {
+                return divergence;
+            }
+        }
+    }
+    None
+}
+
+/// Runs a program on an input, also returning whether it read past the
+/// end of it.
+fn behave(program: &Program, input: &[u8], steps: u64) -> (Behavior, bool) {
+    let io = Exhaust {
+        input,
+        read: 0,
+        past_end: false,
+        output: vec![],
+    };
+    let mut interpreter = Interpreter::with_io(program.clone(), State::new(), io);
+    interpreter.limit_steps(steps);
+    let result = interpreter.run();
+    let (_, io) = interpreter.into_parts();
+    let behavior = Behavior {
+        output: io.output,
+        result,
+    };
+    (behavior, io.past_end)
+}
+
+fn diverges(left: &Behavior, right: &Behavior) -> bool {
+    let stopped = |behavior: &Behavior| matches!(behavior.result, Err(RuntimeError::StepLimit(..)));
+    if stopped(left) || stopped(right) {
+        let len = left.output.len().min(right.output.len());
+        return left.output[..len] != right.output[..len];
+    }
+    let ended_alike = match (&left.result, &right.result) {
+        (Ok(()), Ok(())) => true,
+        (Err(l), Err(r)) => mem::discriminant(l) == mem::discriminant(r),
+        _ => false,
+    };
+    !ended_alike || left.output != right.output
+}
+
+/// What [`analyze`] found about a loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopReport {
+    /// The index of the loop's [`Op::Open`].
+    pub open: usize,
+    /// How many loops it is inside of.
+    pub depth: usize,
+    /// How far each pass through the loop moves the pointer, or `None` if
+    /// a loop inside it moves the pointer by an unknown amount.
+    pub movement: Option<isize>,
+}
+
+impl LoopReport {
+    /// Whether each pass through the loop ends on the cell it started on.
+    pub fn is_balanced(&self) -> bool {
+        self.movement == Some(0)
+    }
+}
+
+/// Facts about a program found without running it, by [`analyze`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// How deeply loops are nested, or 0 if there are none.
+    pub max_depth: usize,
+    /// Every loop, in the order they start.
+    pub loops: Vec<LoopReport>,
+    /// The last cell the pointer can reach, or `None` if it can move
+    /// arbitrarily far. Every cell after it is never touched.
+    pub reach: Option<usize>,
+    /// Whether the program has a `,`.
+    pub reads_input: bool,
+    /// Whether the program has a `.`.
+    pub writes_output: bool,
+}
+
+/// Finds facts about a program without running it.
+pub fn analyze(program: &Program) -> Report {
+    let ops = program.ops();
+    let mut loops = vec![];
+    // The loops which are open, and how far their bodies have moved so far.
+    let mut open: Vec<(usize, Option<isize>)> = vec![];
+    for (i, &op) in ops.iter().enumerate() {
+        let moved = match op {
+            Op::Forward => Some(1),
+            Op::Backward => Some(-1),
+            // The pointer of another tape is somewhere else.
+            Op::PrevTape | Op::NextTape => None,
+            Op::Open(_) => {
+                loops.push(LoopReport {
+                    open: i,
+                    depth: open.len(),
+                    movement: None,
+                });
+                open.push((loops.len() - 1, Some(0)));
+                continue;
+            }
+            Op::Close(_) => {
+                let (index, movement) = open.pop().expect("brackets are matched");
+                loops[index].movement = movement;
+                // A loop which is not balanced runs an unknown number of
+                // times, so it moves the loop it is in by an unknown amount.
+                (movement == Some(0)).then_some(0)
+            }
+            _ => Some(0),
+        };
+        if let Some((_, movement)) = open.last_mut() {
+            *movement = movement
+                .zip(moved)
+                .map(|(movement, moved)| movement + moved);
+        }
+    }
+    let mut reach = 0;
+    walk(ops, &loops, 0, ops.len(), (0, 0), &mut reach);
+    Report {
+        max_depth: loops.iter().map(|l| l.depth + 1).max().unwrap_or(0),
+        loops,
+        reach: (reach != usize::MAX).then_some(reach),
+        reads_input: ops.contains(&Op::In),
+        writes_output: ops.contains(&Op::Out),
+    }
+}
+
+/// Follows the lowest and highest cells the pointer may be on through
+/// `ops[start..end]`, raising `reach` to the highest one it gets to.
+///
+/// A highest cell of `usize::MAX` means the pointer may be anywhere.
+fn walk(
+    ops: &[Op],
+    loops: &[LoopReport],
+    start: usize,
+    end: usize,
+    mut pointer: (usize, usize),
+    reach: &mut usize,
+) -> (usize, usize) {
+    let mut i = start;
+    while i < end {
+        match ops[i] {
+            Op::Forward => {
+                pointer = (pointer.0.saturating_add(1), pointer.1.saturating_add(1));
+                *reach = (*reach).max(pointer.1);
+            }
+            Op::Backward => {
+                let highest = match pointer.1 {
+                    usize::MAX => usize::MAX,
+                    highest => highest.saturating_sub(1),
+                };
+                pointer = (pointer.0.saturating_sub(1), highest);
+            }
+            Op::PrevTape | Op::NextTape => {
+                pointer = (0, usize::MAX);
+                *reach = usize::MAX;
+            }
+            Op::Open(close) if pointer.1 == usize::MAX => i = close,
+            Op::Open(close) => {
+                // The cells the loop may start each pass on, which widen to
+                // every cell on the side it moves towards, and again if a
+                // pass moves past them anyway.
+                let index = loops
+                    .binary_search_by_key(&i, |l| l.open)
+                    .expect("every loop is reported");
+                let movement = loops[index].movement;
+                if movement.is_none_or(|movement| movement < 0) {
+                    pointer.0 = 0;
+                }
+                if movement.is_none_or(|movement| movement > 0) {
+                    pointer.1 = usize::MAX;
+                }
+                loop {
+                    let after = walk(ops, loops, i + 1, close, pointer, reach);
+                    let widened = (
+                        if after.0 < pointer.0 { 0 } else { pointer.0 },
+                        if after.1 > pointer.1 {
+                            usize::MAX
+                        } else {
+                            pointer.1
+                        },
+                    );
+                    if widened == pointer {
+                        break;
+                    }
+                    pointer = widened;
+                }
+                if pointer.1 == usize::MAX {
+                    *reach = usize::MAX;
+                }
+                i = close;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    pointer
+}
@@ -0,0 +1,297 @@
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::iter;
+
+use crate::{AsmError, Instruction};
+
+/// A statement of an assembly program, with its cells resolved.
+enum Statement {
+    Add(usize, u8),
+    Set(usize, u8),
+    Print(usize),
+    Read(usize),
+    While(usize, Vec<Statement>),
+    If(usize, Vec<Statement>),
+}
+
+/// Compiles a program written in a small structured language down to
+/// Brainfuck instructions.
+///
+/// Each line holds one statement, and `#` starts a comment:
+///
+/// - `cell a b` declares cells, which are laid out in memory in order.
+/// - `add a 3` adds a number, which may be negative, to a cell, and
+///   `set a 'H'` sets a cell to a number or a character.
+/// - `print a` writes a cell, and `read a` reads into a cell.
+/// - `while a {` repeats the lines up to the matching `}` while the cell is
+///   not zero, and `if a {` runs them once if it is not zero, leaving the
+///   cell as it was.
+///
+/// The cells after the declared ones are used as temporaries by `if`.
+pub fn compile(source: &str) -> Result<Vec<Instruction>, AsmError> {
+    let mut cells = vec![];
+    // The statements of each open block, along with the line, kind, and
+    // cell of the statement opening them.
+    let mut blocks: Vec<(Vec<Statement>, usize, &str, usize)> = vec![(vec![], 0, "", 0)];
+    for (i, line) in source.lines().enumerate() {
+        let number = i + 1;
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        let cell = |cells: &[String], name: &str| {
+            cells
+                .iter()
+                .position(|cell| cell == name)
+                .ok_or_else(|| AsmError::UnknownCell(number, name.to_string()))
+        };
+        let statement = match keyword {
+            "" => continue,
+            "cell" => {
+                for name in rest.split_whitespace() {
+                    if cells.iter().any(|cell| cell == name) {
+                        return Err(AsmError::DuplicateCell(number, name.to_string()));
+                    }
+                    cells.push(name.to_string());
+                }
+                continue;
+            }
+            "add" | "set" => {
+                let (name, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                let value = value_of(value.trim()).ok_or(AsmError::InvalidValue(number))?;
+                match keyword {
+                    "add" => Statement::Add(cell(&cells, name)?, value),
+                    _ => Statement::Set(cell(&cells, name)?, value),
+                }
+            }
+            "print" => Statement::Print(cell(&cells, rest)?),
+            "read" => Statement::Read(cell(&cells, rest)?),
+            "while" | "if" => {
+                let name = rest
+                    .strip_suffix('{')
+                    .ok_or(AsmError::UnknownStatement(number))?;
+                blocks.push((vec![], number, keyword, cell(&cells, name.trim())?));
+                continue;
+            }
+            "}" if rest.is_empty() => {
+                if blocks.len() == 1 {
+                    return Err(AsmError::UnexpectedClose(number));
+                }
+                let (body, _, kind, cell) = blocks.pop().expect("a block is open");
+                match kind {
+                    "while" => Statement::While(cell, body),
+                    _ => Statement::If(cell, body),
+                }
+            }
+            _ => return Err(AsmError::UnknownStatement(number)),
+        };
+        blocks
+            .last_mut()
+            .expect("the program is a block")
+            .0
+            .push(statement);
+    }
+    if let [_, .., (_, opened, _, _)] = blocks[..] {
+        return Err(AsmError::UnclosedBlock(opened));
+    }
+    let (program, _, _, _) = blocks.pop().expect("the program is a block");
+    let mut compiler = Compiler {
+        pointer: 0,
+        temporaries: cells.len(),
+    };
+    Ok(compiler.block(&program, 0))
+}
+
+/// Parses a number from -255 to 255, or a character, into the value added
+/// to a cell.
+fn value_of(value: &str) -> Option<u8> {
+    if let Some(c) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        let mut chars = c.chars();
+        let (Some(c), None) = (chars.next(), chars.next()) else {
+            return None;
+        };
+        return u8::try_from(u32::from(c)).ok();
+    }
+    let value: i16 = value.parse().ok()?;
+    (-255..=255)
+        .contains(&value)
+        .then(|| value.rem_euclid(256) as u8)
+}
+
+/// Keeps track of where the pointer is while generating instructions.
+struct Compiler {
+    pointer: usize,
+    /// The first cell after the declared ones.
+    temporaries: usize,
+}
+
+impl Compiler {
+    /// Compiles the statements of a block nested `depth` blocks deep.
+    fn block(&mut self, statements: &[Statement], depth: usize) -> Vec<Instruction> {
+        let mut out = vec![];
+        for statement in statements {
+            match statement {
+                Statement::Add(cell, value) => {
+                    self.go(*cell, &mut out);
+                    add(*value, &mut out);
+                }
+                Statement::Set(cell, value) => {
+                    self.go(*cell, &mut out);
+                    out.push(Instruction::Loop(vec![Instruction::Decrement]));
+                    add(*value, &mut out);
+                }
+                Statement::Print(cell) => {
+                    self.go(*cell, &mut out);
+                    out.push(Instruction::Out);
+                }
+                Statement::Read(cell) => {
+                    self.go(*cell, &mut out);
+                    out.push(Instruction::In);
+                }
+                Statement::While(cell, body) => {
+                    self.go(*cell, &mut out);
+                    let mut inner = self.block(body, depth + 1);
+                    self.go(*cell, &mut inner);
+                    out.push(Instruction::Loop(inner));
+                }
+                Statement::If(cell, body) => {
+                    // Moves the cell into two temporaries, moves one back,
+                    // then runs the body once if the other is not zero.
+                    let (flag, copy) = (
+                        self.temporaries + 2 * depth,
+                        self.temporaries + 2 * depth + 1,
+                    );
+                    self.go(*cell, &mut out);
+                    let mut split = vec![Instruction::Decrement];
+                    for target in [flag, copy] {
+                        self.go(target, &mut split);
+                        split.push(Instruction::Increment);
+                    }
+                    self.go(*cell, &mut split);
+                    out.push(Instruction::Loop(split));
+                    self.go(copy, &mut out);
+                    let mut restore = vec![Instruction::Decrement];
+                    self.go(*cell, &mut restore);
+                    restore.push(Instruction::Increment);
+                    self.go(copy, &mut restore);
+                    out.push(Instruction::Loop(restore));
+                    self.go(flag, &mut out);
+                    let mut inner = self.block(body, depth + 1);
+                    self.go(flag, &mut inner);
+                    inner.push(Instruction::Loop(vec![Instruction::Decrement]));
+                    out.push(Instruction::Loop(inner));
+                }
+            }
+        }
+        out
+    }
+
+    /// Moves the pointer to a cell.
+    fn go(&mut self, cell: usize, out: &mut Vec<Instruction>) {
+        let forward = cell > self.pointer;
+        let step = || match forward {
+            true => Instruction::Forward,
+            false => Instruction::Backward,
+        };
+        out.extend(iter::repeat_with(step).take(cell.abs_diff(self.pointer)));
+        self.pointer = cell;
+    }
+}
+
+/// Adds a value to the selected cell with the fewest instructions.
+fn add(value: u8, out: &mut Vec<Instruction>) {
+    if value <= 128 {
+        out.extend(iter::repeat_with(|| Instruction::Increment).take(value.into()));
+    } else {
+        let times = value.wrapping_neg().into();
+        out.extend(iter::repeat_with(|| Instruction::Decrement).take(times));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Buffer, Interpreter, Program, State};
+
+    /// Compiles a program and runs it on an input, returning what it wrote
+    /// and the cells it left.
+    fn run(source: &str, input: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let code: String = compile(source)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let program = Program::parse(&code).unwrap();
+        let mut interpreter =
+            Interpreter::with_io(program, State::new(), Buffer::new(input.to_vec()));
+        interpreter.limit_steps(1_000_000);
+        interpreter.run().unwrap();
+        let (state, io) = interpreter.into_parts();
+        (io.output, state.mem)
+    }
+
+    #[test]
+    fn sets_adds_and_prints_cells() {
+        let (output, mem) = run(
+            "cell a b\nset a 'H'\nprint a\nadd b -3 # wraps\nadd a 1\nprint a",
+            b"",
+        );
+        assert_eq!(output, b"HI");
+        assert_eq!(mem[..2], [b'I', 253]);
+    }
+
+    #[test]
+    fn loops_while_a_cell_is_not_zero() {
+        let source = "
+            cell n out
+            set n 3
+            set out 'a'
+            while n {
+                print out
+                add out 1
+                add n -1
+            }
+        ";
+        assert_eq!(run(source, b"").0, b"abc");
+    }
+
+    #[test]
+    fn runs_if_blocks_once_and_keeps_the_cell() {
+        let source = "
+            cell c
+            read c
+            if c {
+                print c
+                print c
+            }
+            print c
+        ";
+        assert_eq!(run(source, b"x").0, b"xxx");
+        assert_eq!(run(source, b"\0").0, b"\0");
+    }
+
+    #[test]
+    fn reports_the_line_of_errors() {
+        assert_eq!(
+            compile("cell a\nadd b 1").unwrap_err(),
+            AsmError::UnknownCell(2, "b".to_string())
+        );
+        assert_eq!(
+            compile("cell a a").unwrap_err(),
+            AsmError::DuplicateCell(1, "a".to_string())
+        );
+        assert_eq!(
+            compile("cell a\nset a 256").unwrap_err(),
+            AsmError::InvalidValue(2)
+        );
+        assert_eq!(
+            compile("cell a\nwhile a {").unwrap_err(),
+            AsmError::UnclosedBlock(2)
+        );
+        assert_eq!(compile("}").unwrap_err(), AsmError::UnexpectedClose(1));
+        assert_eq!(
+            compile("jump a").unwrap_err(),
+            AsmError::UnknownStatement(1)
+        );
+    }
+}
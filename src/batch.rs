@@ -0,0 +1,145 @@
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{Buffer, Config, Interpreter, OptLevel, Program, RuntimeError, State};
+
+/// The limits applied to each program run by [`run_all`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// The number of steps after which a program is stopped.
+    pub max_steps: Option<u64>,
+    /// The number of cells a program may use.
+    pub max_memory: Option<usize>,
+}
+
+/// How running one program of a batch went.
+#[derive(Debug)]
+pub struct Outcome {
+    /// The bytes the program wrote, even if it failed.
+    pub output: Vec<u8>,
+    /// The number of instructions executed.
+    pub steps: u64,
+    /// The number of cells the program used.
+    pub cells: usize,
+    /// How long the program ran for.
+    pub elapsed: Duration,
+    /// Whether the program halted, or why it failed.
+    pub result: Result<(), RuntimeError>,
+}
+
+/// Runs many programs at once across threads, each with the input at the
+/// same index, returning how each went in the same order.
+///
+/// # Panics
+///
+/// Panics if there are not as many inputs as programs.
+pub fn run_all(
+    programs: &[Program],
+    inputs: &[impl AsRef<[u8]> + Sync],
+    limits: Limits,
+) -> Vec<Outcome> {
+    assert_eq!(
+        programs.len(),
+        inputs.len(),
+        "each program should have an input"
+    );
+    let threads = thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(programs.len());
+    let config = Config {
+        max_steps: limits.max_steps,
+        max_memory: limits.max_memory,
+        ..Config::default()
+    };
+    let next = AtomicUsize::new(0);
+    let outcomes = Mutex::new(Vec::with_capacity(programs.len()));
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(program) = programs.get(index) else {
+                    break;
+                };
+                let outcome = run_one(program, inputs[index].as_ref(), &config);
+                outcomes.lock().unwrap().push((index, outcome));
+            });
+        }
+    });
+    let mut outcomes = outcomes.into_inner().unwrap();
+    outcomes.sort_by_key(|&(index, _)| index);
+    outcomes.into_iter().map(|(_, outcome)| outcome).collect()
+}
+
+fn run_one(program: &Program, input: &[u8], config: &Config) -> Outcome {
+    let mut interpreter = Interpreter::builder()
+        .io(Buffer::new(input.to_vec()))
+        .config(config)
+        .build(program.clone());
+    let (result, report) = interpreter.run_metered();
+    let (_, io) = interpreter.into_parts();
+    Outcome {
+        output: io.output,
+        steps: report.steps,
+        cells: report.peak_memory,
+        elapsed: report.elapsed,
+        result,
+    }
+}
+
+/// How long a program took at one optimization level, in [`bench`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    /// How much the program was optimized.
+    pub level: OptLevel,
+    /// The number of operations left after optimizing.
+    pub len: usize,
+    /// How long optimizing and running the program took.
+    pub elapsed: Duration,
+    /// Whether it wrote the same output as without optimizing.
+    pub same_output: bool,
+}
+
+/// How a program did in [`bench`].
+#[derive(Debug)]
+pub struct Bench {
+    /// How running the program without optimizing went.
+    pub outcome: Outcome,
+    /// How long it took as it was optimized at each level.
+    pub timings: Vec<Timing>,
+}
+
+/// Runs a program without optimizing it, as chosen by `config`, then times
+/// it optimized at each level.
+///
+/// Optimized programs are not checked at every step, so they are only run
+/// if the program halted within the limits and strict mode of `config`.
+pub fn bench(program: &Program, input: &[u8], config: &Config) -> Bench {
+    let outcome = run_one(program, input, config);
+    let mut timings = vec![];
+    if outcome.result.is_ok() {
+        for level in [OptLevel::Basic, OptLevel::Full] {
+            let start = Instant::now();
+            let config = Config {
+                opt_level: level,
+                ..*config
+            };
+            let optimized = program.optimize_with(&config);
+            let mut state = State::with_tapes(config.tapes);
+            if let Some(seed) = config.random {
+                state.rng = seed;
+            }
+            let mut io = Buffer::new(input.to_vec());
+            let result = optimized.run(&mut state, &mut io);
+            timings.push(Timing {
+                level,
+                len: optimized.len(),
+                elapsed: start.elapsed(),
+                same_output: result.is_ok() && io.output == outcome.output,
+            });
+        }
+    }
+    Bench { outcome, timings }
+}
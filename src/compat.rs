@@ -1,9 +1,15 @@
+use alloc::boxed::Box;
+
+use crate::TableError;
+
 const CHAR_TABLE: [[char; 16]; 16] = [
     [
-        ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ',
+        '\0', '\u{1}', '\u{2}', '\u{3}', '\u{4}', '\u{5}', '\u{6}', '\u{7}', '\u{8}', '\t', '\n',
+        '\u{b}', '\u{c}', '\r', '\u{e}', '\u{f}',
     ],
     [
-        ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ',
+        '\u{10}', '\u{11}', '\u{12}', '\u{13}', '\u{14}', '\u{15}', '\u{16}', '\u{17}', '\u{18}',
+        '\u{19}', '\u{1a}', '\u{1b}', '\u{1c}', '\u{1d}', '\u{1e}', '\u{1f}',
     ],
     [
         ' ', '!', '"', '#', '$', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/',
@@ -21,16 +27,18 @@ const CHAR_TABLE: [[char; 16]; 16] = [
         '`', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o',
     ],
     [
-        'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '{', '|', '}', '~', ' ',
+        'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '{', '|', '}', '~', '\u{7f}',
     ],
     [
-        ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ',
+        '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{85}', '\u{86}', '\u{87}', '\u{88}',
+        '\u{89}', '\u{8a}', '\u{8b}', '\u{8c}', '\u{8d}', '\u{8e}', '\u{8f}',
     ],
     [
-        ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ',
+        '\u{90}', '\u{91}', '\u{92}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{97}', '\u{98}',
+        '\u{99}', '\u{9a}', '\u{9b}', '\u{9c}', '\u{9d}', '\u{9e}', '\u{9f}',
     ],
     [
-        '£', '¡', '¢', '£', '¤', '¥', '¦', '§', '¨', '©', 'ª', '«', '¬', '­', '®', '¯',
+        '\u{a0}', '¡', '¢', '£', '¤', '¥', '¦', '§', '¨', '©', 'ª', '«', '¬', '\u{ad}', '®', '¯',
     ],
     [
         '°', '±', '²', '³', '´', 'µ', '¶', '·', '¸', '¹', 'º', '»', '¼', '½', '¾', '¿',
@@ -49,26 +57,320 @@ const CHAR_TABLE: [[char; 16]; 16] = [
     ],
 ];
 
+/// Translates an ISO-8859-1 byte into a character.
 pub fn to_char_8859(b: u8) -> char {
     let d1 = (b / 16) as usize;
     let d2 = (b % 16) as usize;
     CHAR_TABLE[d1][d2]
 }
 
+/// Translates a character into its ISO-8859-1 byte, or `0` if there is none.
 pub fn from_char_8859(c: char) -> u8 {
-    let mut b: Option<u8> = None;
+    try_from_char_8859(c).unwrap_or_default()
+}
+
+/// Translates a character into its ISO-8859-1 byte, if there is one.
+///
+/// ISO-8859-1 shares its code points with the first 256 of Unicode, so
+/// the byte can be found without searching the table.
+pub fn try_from_char_8859(c: char) -> Option<u8> {
+    let b = u8::try_from(c).ok()?;
+    (to_char_8859(b) == c).then_some(b)
+}
+
+/// A table translating the bytes of a Brainfuck process's memory into
+/// characters, and back.
+pub trait Encoding {
+    /// Translates a byte into the character it represents.
+    fn decode(&self, b: u8) -> char;
+    /// Translates a character into the byte representing it, if there is one.
+    fn encode(&self, c: char) -> Option<u8>;
+}
+
+impl<T: Encoding + ?Sized> Encoding for &T {
+    fn decode(&self, b: u8) -> char {
+        (**self).decode(b)
+    }
+    fn encode(&self, c: char) -> Option<u8> {
+        (**self).encode(c)
+    }
+}
+
+impl<T: Encoding + ?Sized> Encoding for Box<T> {
+    fn decode(&self, b: u8) -> char {
+        (**self).decode(b)
+    }
+    fn encode(&self, c: char) -> Option<u8> {
+        (**self).encode(c)
+    }
+}
+
+/// Which control characters to keep when decoding with [`FilterControls`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Controls {
+    /// Keep every control character.
+    #[default]
+    Keep,
+    /// Keep only tab, line feed, and carriage return, which lay out text.
+    Layout,
+    /// Keep no control characters.
+    Hide,
+}
+
+/// Wraps an encoding, replacing the control characters it decodes to
+/// with spaces, unless they are kept.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FilterControls<E> {
+    /// The encoding being filtered.
+    pub encoding: E,
+    /// Which control characters to keep.
+    pub keep: Controls,
+}
+
+impl<E: Encoding> FilterControls<E> {
+    /// Wraps `encoding`, keeping only the given control characters.
+    pub fn new(encoding: E, keep: Controls) -> Self {
+        Self { encoding, keep }
+    }
+}
+
+impl<E: Encoding> Encoding for FilterControls<E> {
+    fn decode(&self, b: u8) -> char {
+        let c = self.encoding.decode(b);
+        let kept = match self.keep {
+            Controls::Keep => true,
+            Controls::Layout => matches!(c, '\t' | '\n' | '\r'),
+            Controls::Hide => false,
+        };
+        if c.is_control() && !kept {
+            ' '
+        } else {
+            c
+        }
+    }
+    fn encode(&self, c: char) -> Option<u8> {
+        self.encoding.encode(c)
+    }
+}
+
+/// The ISO-8859-1 (Latin-1) encoding.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Iso8859_1;
+
+impl Encoding for Iso8859_1 {
+    fn decode(&self, b: u8) -> char {
+        to_char_8859(b)
+    }
+    fn encode(&self, c: char) -> Option<u8> {
+        try_from_char_8859(c)
+    }
+}
+
+const CP437_TABLE: [[char; 16]; 16] = [
+    [
+        '\0', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '\t', '\n', '♂', '♀', '\r', '♫', '☼',
+    ],
+    [
+        '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+    ],
+    [
+        ' ', '!', '"', '#', '$', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/',
+    ],
+    [
+        '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', ':', ';', '<', '=', '>', '?',
+    ],
+    [
+        '@', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O',
+    ],
+    [
+        'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '[', '\\', ']', '^', '_',
+    ],
+    [
+        '`', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o',
+    ],
+    [
+        'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '{', '|', '}', '~', '⌂',
+    ],
+    [
+        'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    ],
+    [
+        'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    ],
+    [
+        'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    ],
+    [
+        '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    ],
+    [
+        '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    ],
+    [
+        '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    ],
+    [
+        'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    ],
+    [
+        '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+    ],
+];
+
+/// Code page 437, the character set of the original IBM PC.
+///
+/// Bytes below `0x20` are drawn as symbols, except for tab, line feed,
+/// and carriage return, which keep their usual meaning so that output
+/// is still laid out in lines.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Cp437;
+
+impl Encoding for Cp437 {
+    fn decode(&self, b: u8) -> char {
+        CP437_TABLE[(b / 16) as usize][(b % 16) as usize]
+    }
+    fn encode(&self, c: char) -> Option<u8> {
+        CP437_TABLE
+            .iter()
+            .flatten()
+            .position(|guess| *guess == c)
+            .map(|b| b as u8)
+    }
+}
+
+/// The bytes `0x80` to `0x9F` of Windows-1252, where it differs from
+/// ISO-8859-1. Bytes not assigned a character are spaces.
+const WINDOWS_1252_TABLE: [char; 32] = [
+    '€', ' ', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', ' ', 'Ž', ' ', ' ', '‘', '’',
+    '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', ' ', 'ž', 'Ÿ',
+];
 
-    for (d1, row) in CHAR_TABLE.iter().enumerate() {
-        for (d2, guess) in row.iter().enumerate() {
-            if c == *guess {
-                b = Some((d1 * 16 + d2) as u8);
-                break;
+/// The Windows-1252 encoding, which adds smart quotes, dashes, and the
+/// euro sign to ISO-8859-1.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Windows1252;
+
+impl Encoding for Windows1252 {
+    fn decode(&self, b: u8) -> char {
+        match b {
+            0x80..=0x9F => WINDOWS_1252_TABLE[(b - 0x80) as usize],
+            _ => to_char_8859(b),
+        }
+    }
+    fn encode(&self, c: char) -> Option<u8> {
+        match c as u32 {
+            0x00..=0x7F | 0xA0..=0xFF => Some(c as u8),
+            _ => WINDOWS_1252_TABLE
+                .iter()
+                .position(|guess| *guess == c && c != ' ')
+                .map(|b| 0x80 + b as u8),
+        }
+    }
+}
+
+/// A character table loaded at runtime, for encodings which are not
+/// built in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    chars: [char; 256],
+}
+
+impl Table {
+    /// Creates a table from the character of each byte.
+    pub fn new(chars: [char; 256]) -> Self {
+        Self { chars }
+    }
+
+    /// Parses a table from text, where each line holds a byte in
+    /// hexadecimal and the character it represents, such as `80 €`.
+    ///
+    /// The character can also be written as a code point, such as
+    /// `U+20AC`. Blank lines and lines starting with `#` are ignored,
+    /// and bytes which are not listed keep their ISO-8859-1 meaning.
+    pub fn from_text(text: &str) -> Result<Self, TableError> {
+        let mut chars = latin1_chars();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim_start();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
             }
+            let invalid = || TableError::InvalidLine(i + 1);
+            let (byte, c) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let byte =
+                u8::from_str_radix(byte.trim_start_matches("0x"), 16).map_err(|_| invalid())?;
+            chars[byte as usize] = parse_char(c.trim()).ok_or_else(invalid)?;
         }
+        Ok(Self { chars })
     }
 
-    match b {
-        Some(b) => b,
-        None => 0,
+    /// Parses a table from JSON, either as an array of 256 single-character
+    /// strings, or as an object mapping bytes to single-character strings,
+    /// such as `{"0x80": "€"}`.
+    ///
+    /// Bytes which are not listed in an object keep their ISO-8859-1 meaning.
+    #[cfg(feature = "json")]
+    pub fn from_json(json: &str) -> Result<Self, TableError> {
+        use serde_json::Value;
+
+        let mut chars = latin1_chars();
+        match serde_json::from_str(json).map_err(TableError::Json)? {
+            Value::Array(entries) => {
+                if entries.len() != 256 {
+                    return Err(TableError::Length(entries.len()));
+                }
+                for (i, entry) in entries.iter().enumerate() {
+                    chars[i] = entry
+                        .as_str()
+                        .and_then(parse_char)
+                        .ok_or(TableError::InvalidEntry(i))?;
+                }
+            }
+            Value::Object(entries) => {
+                for (i, (byte, entry)) in entries.iter().enumerate() {
+                    let byte = match byte.strip_prefix("0x") {
+                        Some(hex) => u8::from_str_radix(hex, 16),
+                        None => byte.parse(),
+                    }
+                    .map_err(|_| TableError::InvalidEntry(i))?;
+                    chars[byte as usize] = entry
+                        .as_str()
+                        .and_then(parse_char)
+                        .ok_or(TableError::InvalidEntry(i))?;
+                }
+            }
+            _ => return Err(TableError::InvalidEntry(0)),
+        }
+        Ok(Self { chars })
+    }
+}
+
+impl Encoding for Table {
+    fn decode(&self, b: u8) -> char {
+        self.chars[b as usize]
+    }
+    fn encode(&self, c: char) -> Option<u8> {
+        self.chars
+            .iter()
+            .position(|guess| *guess == c)
+            .map(|b| b as u8)
+    }
+}
+
+/// The characters of every ISO-8859-1 byte.
+fn latin1_chars() -> [char; 256] {
+    let mut chars = [' '; 256];
+    for (b, c) in chars.iter_mut().enumerate() {
+        *c = to_char_8859(b as u8);
+    }
+    chars
+}
+
+/// Parses a single character, or a code point such as `U+20AC`.
+fn parse_char(s: &str) -> Option<char> {
+    if let Some(hex) = s.strip_prefix("U+").or_else(|| s.strip_prefix("u+")) {
+        return char::from_u32(u32::from_str_radix(hex, 16).ok()?);
     }
+    let mut chars = s.chars();
+    let c = chars.next()?;
+    chars.next().is_none().then_some(c)
 }
@@ -0,0 +1,164 @@
+use crate::CLASSIC_CELLS;
+
+/// What `,` leaves in the cell once there is no more input, as chosen by
+/// [`Config::eof`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Eof {
+    /// Leaves the cell as it was.
+    #[default]
+    Unchanged,
+    /// Sets the cell to 0.
+    Zero,
+    /// Sets the cell to 255, the same as -1.
+    Max,
+}
+
+impl Eof {
+    /// The value of a cell holding `cell` after reading past the end of
+    /// input.
+    pub fn value(self, cell: u8) -> u8 {
+        match self {
+            Eof::Unchanged => cell,
+            Eof::Zero => 0,
+            Eof::Max => u8::MAX,
+        }
+    }
+}
+
+/// How the cells of memory are stored, as chosen by [`Config::tape`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TapeModel {
+    /// Every cell up to the furthest right the pointer has been, in a
+    /// [`State`](crate::State).
+    #[default]
+    Dense,
+    /// Only the pages of cells a program has written to, in a
+    /// [`SparseTape`](crate::sparse::SparseTape), for programs which use
+    /// cells far apart.
+    ///
+    /// Programs are run on a sparse tape by
+    /// [`sparse::run`](crate::sparse::run), which
+    /// [`run_with`](crate::run_with) and
+    /// [`run_from_state_with`](crate::run_from_state_with) use when chosen.
+    /// The state they return still holds every cell up to the last one
+    /// which is not zero, and the program is never optimized.
+    Sparse,
+}
+
+/// How much a program is optimized before it runs, as chosen by
+/// [`Config::opt_level`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OptLevel {
+    /// Runs every instruction as it was written.
+    #[default]
+    None,
+    /// Combines instructions and replaces common loops, like
+    /// [`Program::optimize`](crate::Program::optimize).
+    Basic,
+    /// Also removes code which can never execute and runs the part which
+    /// reads no input ahead of time, like
+    /// [`Program::eliminate_dead_code`](crate::Program::eliminate_dead_code)
+    /// and [`Program::fold_prefix`](crate::Program::fold_prefix).
+    ///
+    /// This assumes the program starts on a new [`State`](crate::State).
+    Full,
+}
+
+/// The choices of how Brainfuck code is parsed, optimized, and run, in one
+/// place.
+///
+/// Optimized programs are not checked at every step, so they are only
+/// used when there are no limits and strict mode is off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Config {
+    /// Whether a leading `#!` line is ignored.
+    pub shebang: bool,
+    /// How much the program is optimized before it runs.
+    pub opt_level: OptLevel,
+    /// The number of steps after which the program fails with
+    /// [`RuntimeError::StepLimit`](crate::RuntimeError::StepLimit).
+    pub max_steps: Option<u64>,
+    /// The number of cells the program may use before it fails with
+    /// [`RuntimeError::MemoryLimit`](crate::RuntimeError::MemoryLimit).
+    pub max_memory: Option<usize>,
+    /// Whether moving left of the first cell or overflowing a cell fails,
+    /// instead of being ignored or wrapping around.
+    pub strict: bool,
+    /// What `,` does once there is no more input.
+    pub eof: Eof,
+    /// How the cells of memory are stored.
+    pub tape: TapeModel,
+    /// How many tapes the program has. With more than one, `{` and `}`
+    /// select the previous and next tape, wrapping around, each with its
+    /// own pointer.
+    pub tapes: usize,
+    /// The seed `?` generates pseudo-random bytes from, each written to the
+    /// cell, or `None` if `?` is not a command. The same seed always gives
+    /// the same bytes.
+    pub random: Option<u64>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            shebang: true,
+            opt_level: OptLevel::None,
+            max_steps: None,
+            max_memory: None,
+            strict: false,
+            eof: Eof::Unchanged,
+            tape: TapeModel::Dense,
+            tapes: 1,
+            random: None,
+        }
+    }
+}
+
+impl Config {
+    /// Whether running needs every step to be checked, so the program runs
+    /// without being optimized.
+    pub fn checks_steps(&self) -> bool {
+        self.max_steps.is_some() || self.max_memory.is_some() || self.strict
+    }
+}
+
+/// A set of semantics matching a well known implementation, filling in
+/// the tape, overflow, and end of input settings of a [`Config`] together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Profile {
+    /// The original implementation by Urban Müller: [`CLASSIC_CELLS`] cells
+    /// which wrap around, leaving the cell unchanged at the end of input.
+    Classic,
+    /// The conventions self-interpreters such as dbfi are written for: as
+    /// many cells as needed, setting the cell to 0 at the end of input, and
+    /// failing instead of relying on cells wrapping around.
+    Dbfi,
+    /// As many cells as needed which wrap around, leaving the cell
+    /// unchanged at the end of input, the same as [`Config::default`].
+    Modern,
+}
+
+impl Profile {
+    /// The default configuration with this profile's semantics.
+    pub fn config(self) -> Config {
+        let config = Config::default();
+        match self {
+            Profile::Classic => Config {
+                max_memory: Some(CLASSIC_CELLS),
+                ..config
+            },
+            Profile::Dbfi => Config {
+                strict: true,
+                eof: Eof::Zero,
+                ..config
+            },
+            Profile::Modern => config,
+        }
+    }
+}
+
+impl From<Profile> for Config {
+    fn from(profile: Profile) -> Self {
+        profile.config()
+    }
+}
@@ -0,0 +1,86 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::Span;
+
+/// How many times each operation of a program was executed.
+///
+/// Collected by an [`Interpreter`](crate::Interpreter) after calling
+/// [`track_coverage`](crate::Interpreter::track_coverage).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    spans: Vec<Span>,
+    hits: Vec<u64>,
+}
+
+impl Coverage {
+    /// Creates a coverage report from the spans of a program's operations
+    /// and their hit counts.
+    pub fn new(spans: Vec<Span>, hits: Vec<u64>) -> Self {
+        assert_eq!(spans.len(), hits.len(), "every span needs a hit count");
+        Self { spans, hits }
+    }
+
+    /// Iterates over the span of each operation and how many times it
+    /// was executed.
+    pub fn iter(&self) -> impl Iterator<Item = (Span, u64)> + '_ {
+        self.spans.iter().copied().zip(self.hits.iter().copied())
+    }
+
+    /// Iterates over the spans of operations which were never executed.
+    pub fn missed(&self) -> impl Iterator<Item = Span> + '_ {
+        self.iter()
+            .filter(|(_, hits)| *hits == 0)
+            .map(|(span, _)| span)
+    }
+
+    /// The number of operations which were executed at least once.
+    pub fn executed(&self) -> usize {
+        self.hits.iter().filter(|hits| **hits > 0).count()
+    }
+
+    /// The number of operations in the program.
+    pub fn total(&self) -> usize {
+        self.hits.len()
+    }
+
+    /// The percentage of operations which were executed at least once.
+    ///
+    /// An empty program is fully covered.
+    pub fn percentage(&self) -> f64 {
+        if self.hits.is_empty() {
+            return 100.0;
+        }
+        self.executed() as f64 * 100.0 / self.total() as f64
+    }
+}
+
+impl fmt::Display for Coverage {
+    /// Summarises the coverage, listing each run of operations which
+    /// was never executed.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "executed {} of {} instructions ({:.1}%)",
+            self.executed(),
+            self.total(),
+            self.percentage()
+        )?;
+        let mut missed: Option<Span> = None;
+        for (span, hits) in self.iter() {
+            match (hits, missed) {
+                (0, Some(run)) => missed = Some(run.to(span)),
+                (0, None) => missed = Some(span),
+                (_, Some(run)) => {
+                    write!(f, "\nnever executed: {run}")?;
+                    missed = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(run) = missed {
+            write!(f, "\nnever executed: {run}")?;
+        }
+        Ok(())
+    }
+}
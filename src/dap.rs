@@ -0,0 +1,374 @@
+use std::fs::read_to_string;
+use std::io::{BufReader, Read, Write};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use serde_json::{json, Value};
+
+use crate::framing::{read_message, write_message};
+use crate::{
+    Breakpoint, Buffer, Chain, Condition, Debugger, Edit, Encoding, Error, Interpreter, Program,
+    RuntimeError, State, Stop,
+};
+
+/// The number of operations executed between checks for new requests while
+/// a program runs.
+const CHUNK: u64 = 10_000;
+
+/// The id of the only thread of a Brainfuck process.
+const THREAD: u64 = 1;
+
+/// The reference to the variables of the tape scope.
+const TAPE: u64 = 1;
+
+/// Serves the Debug Adapter Protocol, so editors can launch and debug a
+/// Brainfuck program.
+///
+/// Requests are read from `input` and responses written to `output`, which
+/// are usually stdin and stdout. The program's output is sent to the editor,
+/// and its input is given by the `input` string of the `launch` request,
+/// translated through `encoding`. Launching also takes the path of the
+/// `program`, and whether to `stopOnEntry`.
+pub fn serve<E: Encoding>(
+    input: impl Read + Send + 'static,
+    output: impl Write,
+    encoding: E,
+) -> Result<(), Error> {
+    let (sender, requests) = mpsc::channel();
+    thread::spawn(move || {
+        let mut input = BufReader::new(input);
+        while let Ok(Some(request)) = read_message(&mut input) {
+            if sender.send(request).is_err() {
+                break;
+            }
+        }
+    });
+    Session::new(output, encoding).serve(&requests)
+}
+
+/// A connection to an editor, debugging at most one program.
+struct Session<W, E> {
+    output: W,
+    encoding: E,
+    seq: u64,
+    path: String,
+    debugger: Option<Debugger<Buffer>>,
+    stop_on_entry: bool,
+    running: bool,
+    done: bool,
+}
+
+impl<W: Write, E: Encoding> Session<W, E> {
+    fn new(output: W, encoding: E) -> Self {
+        Self {
+            output,
+            encoding,
+            seq: 0,
+            path: String::new(),
+            debugger: None,
+            stop_on_entry: false,
+            running: false,
+            done: false,
+        }
+    }
+
+    /// Handles requests until the editor disconnects, running the program
+    /// between them while it is not stopped.
+    fn serve(&mut self, requests: &Receiver<Value>) -> Result<(), Error> {
+        while !self.done {
+            let request = if self.running {
+                match requests.try_recv() {
+                    Ok(request) => Some(request),
+                    Err(TryRecvError::Empty) => None,
+                    Err(TryRecvError::Disconnected) => break,
+                }
+            } else {
+                match requests.recv() {
+                    Ok(request) => Some(request),
+                    Err(_) => break,
+                }
+            };
+            match request {
+                Some(request) => self.handle(&request)?,
+                None => {
+                    if let Some(debugger) = &mut self.debugger {
+                        let outcome = debugger.resume_within(CHUNK);
+                        self.report(outcome, None)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, request: &Value) -> Result<(), Error> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let args = &request["arguments"];
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsConditionalBreakpoints": true,
+                "supportsSetVariable": true,
+            })),
+            "launch" => self.launch(args),
+            "setBreakpoints" => self.set_breakpoints(args),
+            "configurationDone" | "next" | "stepIn" | "stepOut" | "pause" => {
+                self.debugger().map(|_| Value::Null)
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD, "name": "main" }] })),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => Ok(json!({
+                "scopes": [{ "name": "Tape", "variablesReference": TAPE, "expensive": false }],
+            })),
+            "variables" => self.variables(),
+            "setVariable" => self.set_variable(args),
+            "continue" => self
+                .debugger()
+                .map(|_| json!({ "allThreadsContinued": true })),
+            "disconnect" | "terminate" => {
+                self.done = true;
+                Ok(Value::Null)
+            }
+            _ => Err(format!("`{command}` is not supported.")),
+        };
+        let success = result.is_ok();
+        self.respond(request, result)?;
+        if !success {
+            return Ok(());
+        }
+
+        match command {
+            "launch" => self.event("initialized", Value::Null),
+            "configurationDone" if self.stop_on_entry => self.stopped("entry", None),
+            "configurationDone" | "continue" => {
+                self.running = true;
+                Ok(())
+            }
+            "next" | "stepIn" | "stepOut" => {
+                let Some(debugger) = &mut self.debugger else {
+                    return Ok(());
+                };
+                let outcome = debugger
+                    .step()
+                    .map(|()| debugger.interpreter().is_halted().then_some(Stop::Halted));
+                self.report(outcome, Some("step"))
+            }
+            "pause" => {
+                self.running = false;
+                self.stopped("pause", None)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The debugger of the launched program.
+    fn debugger(&mut self) -> Result<&mut Debugger<Buffer>, String> {
+        self.debugger
+            .as_mut()
+            .ok_or_else(|| "No program has been launched.".to_string())
+    }
+
+    fn launch(&mut self, args: &Value) -> Result<Value, String> {
+        let path = args["program"]
+            .as_str()
+            .ok_or("`program` should be the path of a file.")?;
+        let source = read_to_string(path).map_err(|e| e.to_string())?;
+        let program = Program::parse(&source).map_err(|e| e.to_string())?;
+        let input: Vec<u8> = args["input"]
+            .as_str()
+            .unwrap_or_default()
+            .chars()
+            .filter_map(|c| self.encoding.encode(c))
+            .collect();
+        let interpreter = Interpreter::with_io(program, State::new(), Buffer::new(input));
+        self.debugger = Some(Debugger::new(interpreter));
+        self.path = path.to_string();
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or_default();
+        Ok(Value::Null)
+    }
+
+    /// Replaces every breakpoint with those requested.
+    fn set_breakpoints(&mut self, args: &Value) -> Result<Value, String> {
+        let debugger = self.debugger()?;
+        debugger.clear_breakpoints();
+        let mut breakpoints = vec![];
+        for requested in args["breakpoints"].as_array().into_iter().flatten() {
+            let line = requested["line"].as_u64().unwrap_or(1) as usize;
+            let column = requested["column"].as_u64().unwrap_or(1) as usize;
+            let condition = match requested["condition"].as_str() {
+                Some(condition) => match Condition::parse(condition) {
+                    Some(condition) => Some(condition),
+                    None => {
+                        breakpoints.push(json!({
+                            "verified": false,
+                            "message": "The condition could not be understood.",
+                        }));
+                        continue;
+                    }
+                },
+                None => None,
+            };
+            let program = debugger.interpreter().program();
+            let Some(position) = program.position_at(line, column) else {
+                breakpoints.push(json!({
+                    "verified": false,
+                    "message": "There are no instructions after this line.",
+                }));
+                continue;
+            };
+            let span = program.spans()[position];
+            let id = debugger.add_breakpoint(Breakpoint {
+                position,
+                condition,
+            });
+            breakpoints.push(json!({
+                "id": id,
+                "verified": true,
+                "line": span.line,
+                "column": span.column,
+            }));
+        }
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn stack_trace(&mut self) -> Result<Value, String> {
+        let path = self.path.clone();
+        let interpreter = self.debugger()?.interpreter();
+        let program = interpreter.program();
+        let (line, column) = match program.spans().get(interpreter.position()) {
+            Some(span) => (span.line, span.column),
+            None => (program.source().lines().count().max(1), 1),
+        };
+        Ok(json!({
+            "stackFrames": [{
+                "id": 1,
+                "name": "main",
+                "line": line,
+                "column": column,
+                "source": { "path": path },
+            }],
+            "totalFrames": 1,
+        }))
+    }
+
+    /// Lists the pointer and every cell of the tape.
+    fn variables(&mut self) -> Result<Value, String> {
+        let state = self.debugger()?.interpreter().state();
+        let mut variables = vec![json!({
+            "name": "ptr",
+            "value": state.pointer.to_string(),
+            "variablesReference": 0,
+        })];
+        variables.extend(state.mem.iter().enumerate().map(|(index, value)| {
+            json!({
+                "name": format!("[{index}]"),
+                "value": value.to_string(),
+                "variablesReference": 0,
+            })
+        }));
+        Ok(json!({ "variables": variables }))
+    }
+
+    /// Changes the pointer or a cell, named as in [`variables`](Self::variables).
+    fn set_variable(&mut self, args: &Value) -> Result<Value, String> {
+        let name = args["name"].as_str().unwrap_or_default();
+        let value = args["value"].as_str().unwrap_or_default();
+        let target = match name {
+            "ptr" => "@ptr",
+            cell => cell.trim_start_matches('[').trim_end_matches(']'),
+        };
+        let edit = Edit::parse(&format!("set {target} {value}"))
+            .ok_or_else(|| format!("`{value}` cannot be given to `{name}`."))?;
+        let state = self.debugger()?.interpreter_mut().state_mut();
+        edit.apply(state);
+        let value = match edit {
+            Edit::Cell { value, .. } => value.to_string(),
+            Edit::Pointer(pointer) => pointer.to_string(),
+        };
+        Ok(json!({ "value": value }))
+    }
+
+    /// Tells the editor how running the program went, after sending any
+    /// output it wrote.
+    ///
+    /// If the program is still going, the editor is told it stopped for
+    /// `reason`, if one is given.
+    fn report(
+        &mut self,
+        outcome: Result<Option<Stop>, RuntimeError>,
+        reason: Option<&str>,
+    ) -> Result<(), Error> {
+        self.send_output()?;
+        match outcome {
+            Ok(Some(Stop::Halted)) => {
+                self.running = false;
+                self.event("exited", json!({ "exitCode": 0 }))?;
+                self.event("terminated", Value::Null)
+            }
+            Ok(Some(Stop::Breakpoint(id))) => {
+                self.running = false;
+                self.stopped("breakpoint", Some(json!([id])))
+            }
+            Ok(None) => match reason {
+                Some(reason) => self.stopped(reason, None),
+                None => Ok(()),
+            },
+            Err(e) => {
+                self.running = false;
+                self.event(
+                    "stopped",
+                    json!({
+                        "reason": "exception",
+                        "text": Chain(&e).to_string(),
+                        "threadId": THREAD,
+                    }),
+                )
+            }
+        }
+    }
+
+    /// Sends the output the program has written since it was last sent.
+    fn send_output(&mut self) -> Result<(), Error> {
+        let Some(debugger) = &mut self.debugger else {
+            return Ok(());
+        };
+        let bytes = std::mem::take(&mut debugger.interpreter_mut().io_mut().output);
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        let output: String = bytes.iter().map(|&b| self.encoding.decode(b)).collect();
+        self.event("output", json!({ "category": "stdout", "output": output }))
+    }
+
+    fn stopped(&mut self, reason: &str, breakpoints: Option<Value>) -> Result<(), Error> {
+        let mut body = json!({ "reason": reason, "threadId": THREAD });
+        if let Some(breakpoints) = breakpoints {
+            body["hitBreakpointIds"] = breakpoints;
+        }
+        self.event("stopped", body)
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value, String>) -> Result<(), Error> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = message.into(),
+        }
+        self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> Result<(), Error> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn send(&mut self, mut message: Value) -> Result<(), Error> {
+        self.seq += 1;
+        message["seq"] = self.seq.into();
+        Ok(write_message(&mut self.output, &message)?)
+    }
+}
@@ -0,0 +1,534 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use core::iter::Peekable;
+use core::ops::{Bound, RangeBounds};
+use core::str::Chars;
+
+use crate::{Encoding, Interpreter, Io, Pointer, RuntimeError, State};
+
+/// The number of cells on each line of a [`State::dump`].
+const DUMP_WIDTH: usize = 16;
+
+/// A change to make to the state of a paused process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    /// Sets a cell to a value.
+    Cell {
+        /// The location of the cell.
+        index: Pointer,
+        /// The value to give it.
+        value: u8,
+    },
+    /// Moves the pointer to a cell.
+    Pointer(Pointer),
+}
+
+impl Edit {
+    /// Parses an edit written as `set <target> <value>`.
+    ///
+    /// The target is the location of a cell, or `@ptr` for the pointer.
+    /// Values can be written in decimal, in hexadecimal starting with `0x`,
+    /// or as a character in single quotes, such as `set 3 'A'`.
+    pub fn parse(command: &str) -> Option<Self> {
+        let mut words = command.split_whitespace();
+        if words.next()? != "set" {
+            return None;
+        }
+        let target = words.next()?;
+        let value = words.next()?;
+        if words.next().is_some() {
+            return None;
+        }
+        if target == "@ptr" {
+            return Some(Edit::Pointer(parse_number(value)?));
+        }
+        Some(Edit::Cell {
+            index: parse_number(target)?,
+            value: parse_value(value)?,
+        })
+    }
+
+    /// Applies the edit to a state.
+    pub fn apply(&self, state: &mut State) {
+        match *self {
+            Edit::Cell { index, value } => {
+                if index >= state.mem.len() {
+                    state.mem.resize(index + 1, 0);
+                }
+                state.mem[index] = value;
+            }
+            Edit::Pointer(pointer) => {
+                state.pointer = pointer;
+                state.cell_mut();
+            }
+        }
+    }
+}
+
+fn parse_number(s: &str) -> Option<usize> {
+    match s.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+fn parse_value(s: &str) -> Option<u8> {
+    if let Some(c) = s.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+        let mut chars = c.chars();
+        let c = chars.next()?;
+        return match chars.next() {
+            None => u8::try_from(c).ok(),
+            Some(_) => None,
+        };
+    }
+    u8::try_from(parse_number(s)?).ok()
+}
+
+impl State {
+    /// Formats the cells in `range` as a hexdump, with each line showing the
+    /// location of its first cell, the cells in hexadecimal, and the cells
+    /// decoded through `encoding`.
+    ///
+    /// The selected cell is marked with brackets, and characters which cannot
+    /// be printed are shown as `.`. An unbounded range ends at the last cell
+    /// in memory or the pointer, whichever is further.
+    pub fn dump(&self, range: impl RangeBounds<Pointer>, encoding: &impl Encoding) -> String {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.mem.len().max(self.pointer + 1),
+        };
+
+        let mut dump = String::new();
+        for line in (start..end).step_by(DUMP_WIDTH) {
+            let cells = line..(line + DUMP_WIDTH).min(end);
+            let _ = write!(dump, "{line:08x} ");
+            for index in cells.clone() {
+                let value = self.mem.get(index).copied().unwrap_or(0);
+                let _ = if index == self.pointer {
+                    write!(dump, "[{value:02x}]")
+                } else {
+                    write!(dump, " {value:02x} ")
+                };
+            }
+            for _ in cells.len()..DUMP_WIDTH {
+                dump.push_str("    ");
+            }
+            dump.push_str(" |");
+            for index in cells {
+                let c = encoding.decode(self.mem.get(index).copied().unwrap_or(0));
+                dump.push(if c.is_control() { '.' } else { c });
+            }
+            dump.push_str("|\n");
+        }
+        dump
+    }
+}
+
+/// A value a [`Condition`] can compare.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value {
+    /// A number, written in decimal, in hexadecimal starting with `0x`, or
+    /// as a character in single quotes.
+    Number(usize),
+    /// The location of the pointer, written `ptr`.
+    Pointer,
+    /// The selected cell, written `cell`.
+    Cell,
+    /// The cell at a location, such as `cell[3]`.
+    CellAt(Pointer),
+}
+
+impl Value {
+    fn eval(self, state: &State) -> usize {
+        match self {
+            Value::Number(n) => n,
+            Value::Pointer => state.pointer,
+            Value::Cell => state.cell().into(),
+            Value::CellAt(index) => state.mem.get(index).copied().unwrap_or(0).into(),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{n}"),
+            Value::Pointer => write!(f, "ptr"),
+            Value::Cell => write!(f, "cell"),
+            Value::CellAt(index) => write!(f, "cell[{index}]"),
+        }
+    }
+}
+
+/// How a [`Condition`] compares two values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    /// `==`
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        })
+    }
+}
+
+/// A condition on the state of a process, such as `cell[3] > 100 && ptr == 3`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    /// Holds if the comparison between two values does.
+    Compare(Value, Comparison, Value),
+    /// Holds if both conditions do, written `&&`.
+    And(Box<Condition>, Box<Condition>),
+    /// Holds if either condition does, written `||`.
+    Or(Box<Condition>, Box<Condition>),
+}
+
+impl Condition {
+    /// Parses a condition.
+    ///
+    /// Comparisons can be joined with `&&` and `||`, where `&&` binds more
+    /// tightly, and grouped with parentheses.
+    pub fn parse(condition: &str) -> Option<Self> {
+        let mut chars = condition.chars().peekable();
+        let condition = parse_or(&mut chars)?;
+        skip_spaces(&mut chars);
+        match chars.next() {
+            None => Some(condition),
+            Some(_) => None,
+        }
+    }
+
+    /// Whether the condition holds for a state.
+    pub fn eval(&self, state: &State) -> bool {
+        match self {
+            Condition::Compare(left, comparison, right) => {
+                let (left, right) = (left.eval(state), right.eval(state));
+                match comparison {
+                    Comparison::Eq => left == right,
+                    Comparison::Ne => left != right,
+                    Comparison::Lt => left < right,
+                    Comparison::Le => left <= right,
+                    Comparison::Gt => left > right,
+                    Comparison::Ge => left >= right,
+                }
+            }
+            Condition::And(left, right) => left.eval(state) && right.eval(state),
+            Condition::Or(left, right) => left.eval(state) || right.eval(state),
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::Compare(left, comparison, right) => write!(f, "{left} {comparison} {right}"),
+            Condition::And(left, right) => {
+                write_grouped(f, left)?;
+                write!(f, " && ")?;
+                write_grouped(f, right)
+            }
+            Condition::Or(left, right) => write!(f, "{left} || {right}"),
+        }
+    }
+}
+
+/// Writes a side of `&&`, in parentheses if it is joined by `||`.
+fn write_grouped(f: &mut fmt::Formatter<'_>, condition: &Condition) -> fmt::Result {
+    match condition {
+        Condition::Or(..) => write!(f, "({condition})"),
+        _ => write!(f, "{condition}"),
+    }
+}
+
+type Input<'a> = Peekable<Chars<'a>>;
+
+fn skip_spaces(chars: &mut Input) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+/// Consumes `token` if it is next, after any spaces.
+fn eat(chars: &mut Input, token: &str) -> bool {
+    skip_spaces(chars);
+    let mut ahead = chars.clone();
+    if token.chars().all(|c| ahead.next() == Some(c)) {
+        *chars = ahead;
+        true
+    } else {
+        false
+    }
+}
+
+fn parse_or(chars: &mut Input) -> Option<Condition> {
+    let mut condition = parse_and(chars)?;
+    while eat(chars, "||") {
+        condition = Condition::Or(Box::new(condition), Box::new(parse_and(chars)?));
+    }
+    Some(condition)
+}
+
+fn parse_and(chars: &mut Input) -> Option<Condition> {
+    let mut condition = parse_comparison(chars)?;
+    while eat(chars, "&&") {
+        condition = Condition::And(Box::new(condition), Box::new(parse_comparison(chars)?));
+    }
+    Some(condition)
+}
+
+fn parse_comparison(chars: &mut Input) -> Option<Condition> {
+    if eat(chars, "(") {
+        let condition = parse_or(chars)?;
+        return eat(chars, ")").then_some(condition);
+    }
+    let left = parse_term(chars)?;
+    let comparison = [
+        ("==", Comparison::Eq),
+        ("!=", Comparison::Ne),
+        ("<=", Comparison::Le),
+        (">=", Comparison::Ge),
+        ("<", Comparison::Lt),
+        (">", Comparison::Gt),
+    ]
+    .into_iter()
+    .find_map(|(token, comparison)| eat(chars, token).then_some(comparison))?;
+    let right = parse_term(chars)?;
+    Some(Condition::Compare(left, comparison, right))
+}
+
+fn parse_term(chars: &mut Input) -> Option<Value> {
+    skip_spaces(chars);
+    let mut word = String::new();
+    if chars.next_if_eq(&'\'').is_some() {
+        return match (chars.next(), chars.next()) {
+            (Some(c), Some('\'')) => Some(Value::Number(u8::try_from(c).ok()?.into())),
+            _ => None,
+        };
+    }
+    while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric()) {
+        word.push(c);
+    }
+    match word.as_str() {
+        "ptr" => Some(Value::Pointer),
+        "cell" if eat(chars, "[") => {
+            let index = parse_term(chars)?;
+            match (index, eat(chars, "]")) {
+                (Value::Number(index), true) => Some(Value::CellAt(index)),
+                _ => None,
+            }
+        }
+        "cell" => Some(Value::Cell),
+        _ => parse_number(&word).map(Value::Number),
+    }
+}
+
+/// A location where a [`Debugger`] stops, if its condition holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    /// The index of the operation to stop before.
+    pub position: usize,
+    /// The condition which must hold when the operation is reached, if any.
+    pub condition: Option<Condition>,
+}
+
+/// Why a [`Debugger`] stopped running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    /// The breakpoint with this id was reached.
+    Breakpoint(usize),
+    /// The program halted.
+    Halted,
+}
+
+/// Runs an [`Interpreter`] until it reaches one of its breakpoints.
+#[derive(Debug)]
+pub struct Debugger<I: Io> {
+    interpreter: Interpreter<I>,
+    breakpoints: Vec<Option<Breakpoint>>,
+    /// The position last stopped at by a breakpoint, until the next step.
+    stopped: Option<usize>,
+}
+
+impl<I: Io> Debugger<I> {
+    /// Creates a debugger for an interpreter, without any breakpoints.
+    pub fn new(interpreter: Interpreter<I>) -> Self {
+        Self {
+            interpreter,
+            breakpoints: Vec::new(),
+            stopped: None,
+        }
+    }
+
+    /// The interpreter being debugged.
+    pub fn interpreter(&self) -> &Interpreter<I> {
+        &self.interpreter
+    }
+
+    /// The interpreter being debugged, which can be changed.
+    pub fn interpreter_mut(&mut self) -> &mut Interpreter<I> {
+        &mut self.interpreter
+    }
+
+    /// Consumes the debugger, returning the interpreter.
+    pub fn into_interpreter(self) -> Interpreter<I> {
+        self.interpreter
+    }
+
+    /// Adds a breakpoint, returning its id.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+        self.breakpoints.push(Some(breakpoint));
+        self.breakpoints.len() - 1
+    }
+
+    /// Removes the breakpoint with an id, returning it if there was one.
+    pub fn remove_breakpoint(&mut self, id: usize) -> Option<Breakpoint> {
+        self.breakpoints.get_mut(id)?.take()
+    }
+
+    /// The breakpoints along with their ids.
+    pub fn breakpoints(&self) -> impl Iterator<Item = (usize, &Breakpoint)> {
+        self.breakpoints
+            .iter()
+            .enumerate()
+            .filter_map(|(id, breakpoint)| Some((id, breakpoint.as_ref()?)))
+    }
+
+    /// Executes the next operation, flushing the output if the program halts.
+    pub fn step(&mut self) -> Result<(), RuntimeError> {
+        self.stopped = None;
+        self.interpreter.step()?;
+        if self.interpreter.is_halted() {
+            self.interpreter.io_mut().flush()?;
+        }
+        Ok(())
+    }
+
+    /// Removes every breakpoint.
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Executes operations until a breakpoint is reached whose condition
+    /// holds, or the program halts.
+    ///
+    /// A breakpoint before the next operation stops the debugger straight
+    /// away, unless it was just stopped at, so that it can be resumed from.
+    pub fn resume(&mut self) -> Result<Stop, RuntimeError> {
+        loop {
+            if let Some(stop) = self.resume_within(u64::MAX)? {
+                return Ok(stop);
+            }
+        }
+    }
+
+    /// Like [`resume`](Self::resume), but executes at most `steps`
+    /// operations, returning `None` if neither a breakpoint nor the end of
+    /// the program was reached.
+    pub fn resume_within(&mut self, steps: u64) -> Result<Option<Stop>, RuntimeError> {
+        for _ in 0..steps {
+            if self.interpreter.is_halted() {
+                return Ok(Some(Stop::Halted));
+            }
+            let position = self.interpreter.position();
+            if self.stopped != Some(position) {
+                if let Some(id) = self.hit() {
+                    self.stopped = Some(position);
+                    return Ok(Some(Stop::Breakpoint(id)));
+                }
+            }
+            self.step()?;
+        }
+        Ok(None)
+    }
+
+    /// The id of a breakpoint before the next operation whose condition holds.
+    fn hit(&self) -> Option<usize> {
+        let position = self.interpreter.position();
+        let state = self.interpreter.state();
+        self.breakpoints().find_map(|(id, breakpoint)| {
+            let holds = breakpoint
+                .condition
+                .as_ref()
+                .is_none_or(|condition| condition.eval(state));
+            (breakpoint.position == position && holds).then_some(id)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Buffer, Program};
+
+    fn debugger(code: &str) -> Debugger<Buffer> {
+        let program = Program::parse(code).unwrap();
+        Debugger::new(Interpreter::with_io(
+            program,
+            State::new(),
+            Buffer::default(),
+        ))
+    }
+
+    #[test]
+    fn stops_before_the_first_operation() {
+        let mut debugger = debugger("+++.");
+        let id = debugger.add_breakpoint(Breakpoint {
+            position: 0,
+            condition: None,
+        });
+        assert_eq!(debugger.resume().unwrap(), Stop::Breakpoint(id));
+        assert_eq!(debugger.interpreter().steps(), 0);
+        assert_eq!(debugger.resume().unwrap(), Stop::Halted);
+        assert_eq!(debugger.interpreter().io().output, [3]);
+    }
+
+    #[test]
+    fn stops_each_time_a_loop_reaches_a_breakpoint() {
+        let mut debugger = debugger("+++[-]");
+        debugger.add_breakpoint(Breakpoint {
+            position: 4,
+            condition: None,
+        });
+        let mut stops = 0;
+        while debugger.resume().unwrap() != Stop::Halted {
+            stops += 1;
+        }
+        assert_eq!(stops, 3);
+    }
+
+    #[test]
+    fn only_stops_where_the_condition_holds() {
+        let mut debugger = debugger("+++[-]");
+        debugger.add_breakpoint(Breakpoint {
+            position: 4,
+            condition: Condition::parse("cell == 1"),
+        });
+        assert!(matches!(debugger.resume(), Ok(Stop::Breakpoint(_))));
+        assert_eq!(debugger.interpreter().state().cell(), 1);
+        assert_eq!(debugger.resume().unwrap(), Stop::Halted);
+    }
+}
@@ -0,0 +1,192 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+use crate::{Pointer, State};
+
+/// A cell which holds a different value in two states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellChange {
+    /// The tape the cell is on, which is always 0 unless there are several.
+    pub tape: usize,
+    /// The location of the cell.
+    pub index: Pointer,
+    /// The value in the earlier state.
+    pub before: u8,
+    /// The value in the later state.
+    pub after: u8,
+}
+
+/// The differences between two snapshots of a Brainfuck process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateDiff {
+    /// The cells which changed on every tape, in order of tape then location.
+    pub cells: Vec<CellChange>,
+    /// The location of the pointer in the earlier state, on its selected tape.
+    pub pointer_before: Pointer,
+    /// The location of the pointer in the later state, on its selected tape.
+    pub pointer_after: Pointer,
+    /// The bytes written between the states.
+    pub output: Vec<u8>,
+}
+
+impl StateDiff {
+    /// Whether nothing changed between the states.
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty() && self.pointer_before == self.pointer_after && self.output.is_empty()
+    }
+
+    /// How far the pointer moved, negative if it moved backward.
+    pub fn moved(&self) -> isize {
+        self.pointer_after as isize - self.pointer_before as isize
+    }
+
+    /// The locations of the cells which changed.
+    pub fn changed(&self) -> impl Iterator<Item = Pointer> + '_ {
+        self.cells.iter().map(|change| change.index)
+    }
+
+    /// Whether every cell which changed lies in `range`.
+    pub fn only_within(&self, range: Range<Pointer>) -> bool {
+        self.changed().all(|index| range.contains(&index))
+    }
+}
+
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.cells {
+            if change.tape > 0 {
+                write!(f, "tape {} ", change.tape)?;
+            }
+            writeln!(
+                f,
+                "cell {}: {} -> {}",
+                change.index, change.before, change.after
+            )?;
+        }
+        writeln!(
+            f,
+            "pointer: {} -> {}",
+            self.pointer_before, self.pointer_after
+        )?;
+        match self.output.len() {
+            1 => write!(f, "wrote 1 byte: {:?}", self.output),
+            n => write!(f, "wrote {n} bytes: {:?}", self.output),
+        }
+    }
+}
+
+/// The memory of a tape, whether it is selected or banked.
+fn memory(state: &State, tape: usize) -> &[u8] {
+    if tape == state.bank {
+        &state.mem
+    } else {
+        state.banks.get(tape).map_or(&[], |(mem, _)| mem)
+    }
+}
+
+impl State {
+    /// Compares this state with a later snapshot of the same process, given
+    /// `output`, every byte the process has written since it started.
+    ///
+    /// Every tape is compared, with cells past the end of either memory
+    /// treated as zero.
+    pub fn diff(&self, later: &State, output: &[u8]) -> StateDiff {
+        let mut cells = Vec::new();
+        for tape in 0..self.tapes().max(later.tapes()) {
+            let (before, after) = (memory(self, tape), memory(later, tape));
+            for index in 0..before.len().max(after.len()) {
+                let before = before.get(index).copied().unwrap_or(0);
+                let after = after.get(index).copied().unwrap_or(0);
+                if before != after {
+                    cells.push(CellChange {
+                        tape,
+                        index,
+                        before,
+                        after,
+                    });
+                }
+            }
+        }
+        let written = self.written.min(output.len())..later.written.min(output.len());
+        StateDiff {
+            cells,
+            pointer_before: self.pointer,
+            pointer_after: later.pointer,
+            output: output.get(written).unwrap_or_default().to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Buffer, Config, Interpreter, Program};
+
+    /// The state a program leaves, along with every byte it wrote.
+    fn run(code: &str, tapes: usize) -> (State, Vec<u8>) {
+        let config = Config {
+            tapes,
+            ..Config::default()
+        };
+        let program = Program::parse_with(code, &config).unwrap();
+        let mut interpreter = Interpreter::builder()
+            .io(Buffer::default())
+            .config(&config)
+            .build(program);
+        interpreter.run().unwrap();
+        let (state, io) = interpreter.into_parts();
+        (state, io.output)
+    }
+
+    #[test]
+    fn finds_changed_cells_and_moves() {
+        let (before, _) = run("+>++>+++", 1);
+        let (after, output) = run("+>++>+++>>-.<<<<[-]", 1);
+        let diff = before.diff(&after, &output);
+        let change = |index, before, after| CellChange {
+            tape: 0,
+            index,
+            before,
+            after,
+        };
+        assert_eq!(diff.cells, [change(0, 1, 0), change(4, 0, 255)]);
+        assert_eq!(
+            (diff.pointer_before, diff.pointer_after, diff.moved()),
+            (2, 0, -2)
+        );
+        assert_eq!(diff.output, [255]);
+        assert!(diff.only_within(0..5));
+        assert!(!diff.only_within(1..5));
+        assert_eq!(
+            diff.to_string(),
+            "cell 0: 1 -> 0\ncell 4: 0 -> 255\npointer: 2 -> 0\nwrote 1 byte: [255]"
+        );
+    }
+
+    #[test]
+    fn compares_a_state_with_itself() {
+        let (state, output) = run("+++.>+.", 1);
+        let diff = state.diff(&state, &output);
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "pointer: 1 -> 1\nwrote 0 bytes: []");
+    }
+
+    #[test]
+    fn compares_every_tape() {
+        let (before, _) = run("+}+", 2);
+        let (after, output) = run("+}+{++}+.", 2);
+        let diff = before.diff(&after, &output);
+        let change = |tape, before, after| CellChange {
+            tape,
+            index: 0,
+            before,
+            after,
+        };
+        assert_eq!(diff.cells, [change(0, 1, 3), change(1, 1, 2)]);
+        assert_eq!(diff.output, [2]);
+        assert!(diff
+            .to_string()
+            .starts_with("cell 0: 1 -> 3\ntape 1 cell 0: 1 -> 2\n"));
+    }
+}
@@ -0,0 +1,306 @@
+use alloc::string::String;
+use core::fmt;
+
+use crate::{Span, Spin};
+
+/// The error produced when an [`Io`](crate::Io) fails to read or write.
+#[cfg(feature = "std")]
+pub use std::io::Error as IoError;
+
+/// The error produced when an [`Io`](crate::Io) fails to read or write.
+///
+/// Without the `std` feature, no more detail is available.
+#[cfg(not(feature = "std"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoError;
+
+#[cfg(not(feature = "std"))]
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Could not read or write.")
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl core::error::Error for IoError {}
+
+/// Any error produced by this crate.
+#[derive(Debug)]
+pub enum Error {
+    /// Brainfuck code could not be parsed.
+    Parse(ParseError),
+    /// A Brainfuck program failed while running.
+    Runtime(RuntimeError),
+    /// Reading or writing something other than the program's own input
+    /// and output failed, such as loading a source file.
+    Io(IoError),
+    /// A character table could not be loaded.
+    Table(TableError),
+    /// An input log could not be loaded.
+    Log(LogError),
+    /// An assembly program could not be compiled.
+    Asm(AsmError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(_) => write!(f, "Could not parse the program"),
+            Error::Runtime(_) => write!(f, "The program failed"),
+            Error::Io(_) => write!(f, "Could not read or write a file"),
+            Error::Table(_) => write!(f, "Could not load the character table"),
+            Error::Log(_) => write!(f, "Could not load the input log"),
+            Error::Asm(_) => write!(f, "Could not compile the assembly"),
+        }
+    }
+}
+
+// The errors of this crate only describe what they were doing, leaving the
+// message of any error they wrap to their source, so reporters walking the
+// chain print each message once.
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Error::Parse(e) => Some(e),
+            Error::Runtime(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Table(e) => Some(e),
+            Error::Log(e) => Some(e),
+            Error::Asm(e) => Some(e),
+        }
+    }
+}
+
+/// Writes an error followed by every error which caused it, each after a
+/// colon, such as `The program failed: A cell went past 0 or 255.`
+pub struct Chain<'a>(pub &'a dyn core::error::Error);
+
+impl fmt::Display for Chain<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)?;
+        let mut source = self.0.source();
+        while let Some(e) = source {
+            write!(f, ": {e}")?;
+            source = e.source();
+        }
+        Ok(())
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(e: RuntimeError) -> Self {
+        Error::Runtime(e)
+    }
+}
+
+impl From<IoError> for Error {
+    fn from(e: IoError) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<TableError> for Error {
+    fn from(e: TableError) -> Self {
+        Error::Table(e)
+    }
+}
+
+impl From<LogError> for Error {
+    fn from(e: LogError) -> Self {
+        Error::Log(e)
+    }
+}
+
+impl From<AsmError> for Error {
+    fn from(e: AsmError) -> Self {
+        Error::Asm(e)
+    }
+}
+
+/// An error encountered while compiling a program with
+/// [`asm::compile`](crate::asm::compile), along with the line it is on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmError {
+    /// A line does not hold a known statement.
+    UnknownStatement(usize),
+    /// A cell is used without being declared.
+    UnknownCell(usize, String),
+    /// A cell is declared more than once.
+    DuplicateCell(usize, String),
+    /// A value is not a number from -255 to 255 or a single character.
+    InvalidValue(usize),
+    /// A block is opened on a line but never closed.
+    UnclosedBlock(usize),
+    /// A `}` closes no block.
+    UnexpectedClose(usize),
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsmError::UnknownStatement(line) => write!(f, "Line {line} is not a statement."),
+            AsmError::UnknownCell(line, name) => {
+                write!(
+                    f,
+                    "Line {line} uses `{name}`, which is not a declared cell."
+                )
+            }
+            AsmError::DuplicateCell(line, name) => {
+                write!(f, "Line {line} declares `{name}`, which is already a cell.")
+            }
+            AsmError::InvalidValue(line) => {
+                write!(f, "Line {line} should give a number or a character.")
+            }
+            AsmError::UnclosedBlock(line) => {
+                write!(f, "The block opened on line {line} is never closed.")
+            }
+            AsmError::UnexpectedClose(line) => write!(f, "Line {line} closes no block."),
+        }
+    }
+}
+
+impl core::error::Error for AsmError {}
+
+/// An error encountered while loading a [`Table`](crate::Table).
+#[derive(Debug)]
+pub enum TableError {
+    /// A line of a text table could not be understood.
+    InvalidLine(usize),
+    /// An entry of a JSON table could not be understood.
+    InvalidEntry(usize),
+    /// A JSON array did not have exactly 256 entries.
+    Length(usize),
+    /// A JSON table was not valid JSON, or neither an array nor an object.
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for TableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TableError::InvalidLine(line) => {
+                write!(f, "Line {line} should be a byte followed by a character.")
+            }
+            TableError::InvalidEntry(entry) => {
+                write!(f, "Entry {entry} should map a byte to a single character.")
+            }
+            TableError::Length(len) => write!(f, "Expected 256 characters, found {len}."),
+            #[cfg(feature = "json")]
+            TableError::Json(_) => write!(f, "Could not read table"),
+        }
+    }
+}
+
+impl core::error::Error for TableError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "json")]
+            TableError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// An error encountered while loading an input log for [`Replay`](crate::Replay).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogError {
+    /// A line of the log could not be understood.
+    InvalidLine(usize),
+}
+
+impl fmt::Display for LogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogError::InvalidLine(line) => {
+                write!(f, "Line {line} should be a byte or `eof`.")
+            }
+        }
+    }
+}
+
+impl core::error::Error for LogError {}
+
+/// An error encountered while parsing Brainfuck code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// A character which is not a Brainfuck instruction.
+    InvalidCharacter(char),
+    /// A `[` which is never closed.
+    UnmatchedOpen(Span),
+    /// A `]` which closes nothing.
+    UnmatchedClose(Span),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::InvalidCharacter(c) => write!(f, "`{c}` is not an instruction."),
+            ParseError::UnmatchedOpen(span) => write!(f, "{span}: `[` is never closed."),
+            ParseError::UnmatchedClose(span) => write!(f, "{span}: `]` has no matching `[`."),
+        }
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+/// An error encountered while running a Brainfuck program.
+#[derive(Debug)]
+pub enum RuntimeError {
+    /// Reading input or writing output failed.
+    Io(IoError),
+    /// The program did not halt within the given number of steps, along
+    /// with the loop it was running, if known.
+    StepLimit(u64, Option<Spin>),
+    /// The program tried to use more than the given number of cells.
+    MemoryLimit(usize),
+    /// In strict mode, `<` was run on the first cell.
+    PointerUnderflow,
+    /// In strict mode, a cell was incremented past 255 or decremented past 0.
+    CellOverflow,
+    /// The run was stopped through a [`CancelToken`](crate::CancelToken).
+    Cancelled,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::Io(_) => write!(f, "The program's input or output failed"),
+            RuntimeError::StepLimit(steps, None) => {
+                write!(f, "The program did not halt within {steps} steps.")
+            }
+            RuntimeError::StepLimit(steps, Some(spin)) => {
+                write!(
+                    f,
+                    "The program did not halt within {steps} steps, running {spin}."
+                )
+            }
+            RuntimeError::MemoryLimit(cells) => {
+                write!(f, "The program tried to use more than {cells} cells.")
+            }
+            RuntimeError::PointerUnderflow => write!(f, "`<` was run on the first cell."),
+            RuntimeError::CellOverflow => write!(f, "A cell went past 0 or 255."),
+            RuntimeError::Cancelled => write!(f, "The program was cancelled."),
+        }
+    }
+}
+
+impl core::error::Error for RuntimeError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            RuntimeError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<IoError> for RuntimeError {
+    fn from(e: IoError) -> Self {
+        RuntimeError::Io(e)
+    }
+}
@@ -0,0 +1,212 @@
+use crate::{ParseError, Span, CLASSIC_CELLS};
+
+/// The number of cells code evaluated by [`eval`] can use.
+pub const CELLS: usize = CLASSIC_CELLS;
+
+/// Evaluates input-free Brainfuck code at compile time, expanding to the
+/// bytes it writes as a `&'static [u8; N]`.
+///
+/// The code is run by [`eval`](crate::eval::eval), and fails to compile
+/// if it is not valid, reads input, or moves past the last of the
+/// [`CELLS`](crate::eval::CELLS) cells.
+#[macro_export]
+macro_rules! eval_bf {
+    ($code:expr) => {{
+        const LEN: usize = $crate::eval::output_len($code);
+        const OUTPUT: [u8; LEN] = $crate::eval::eval::<LEN>($code).0;
+        &OUTPUT
+    }};
+}
+
+/// Checks that code can be parsed, failing with the same error as
+/// [`Program::parse`](crate::Program::parse), in a `const` context.
+pub const fn validate(code: &str) -> Result<(), ParseError> {
+    let code = code.as_bytes();
+    let mut depth = 0usize;
+    let mut i = commands_start(code);
+    while i < code.len() {
+        match code[i] {
+            b'[' => depth += 1,
+            b']' if depth == 0 => return Err(ParseError::UnmatchedClose(span_at(code, i))),
+            b']' => depth -= 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    if depth == 0 {
+        return Ok(());
+    }
+    // The last `[` left open, which is the one the parser reports. Every
+    // bracket after it is matched.
+    let mut pending = 0usize;
+    let mut i = code.len();
+    loop {
+        i -= 1;
+        match code[i] {
+            b']' => pending += 1,
+            b'[' if pending == 0 => return Err(ParseError::UnmatchedOpen(span_at(code, i))),
+            b'[' => pending -= 1,
+            _ => {}
+        }
+    }
+}
+
+/// Runs input-free code on [`CELLS`] wrapping cells in a `const` context,
+/// returning as much of its output as fits in `N` bytes along with how
+/// many bytes it wrote in total.
+///
+/// # Panics
+///
+/// Panics if the code is not valid, reads input, or moves past the last
+/// cell, which fails to compile when evaluated at compile time.
+pub const fn eval<const N: usize>(code: &str) -> ([u8; N], usize) {
+    if validate(code).is_err() {
+        panic!("the code has a bracket without a partner");
+    }
+    let code = code.as_bytes();
+    let mut mem = [0u8; CELLS];
+    let mut output = [0u8; N];
+    let (mut pointer, mut written) = (0, 0);
+    let mut i = commands_start(code);
+    while i < code.len() {
+        match code[i] {
+            b'+' => mem[pointer] = mem[pointer].wrapping_add(1),
+            b'-' => mem[pointer] = mem[pointer].wrapping_sub(1),
+            b'>' => {
+                pointer += 1;
+                if pointer == CELLS {
+                    panic!("the code moved past the last cell");
+                }
+            }
+            b'<' => pointer = pointer.saturating_sub(1),
+            b'[' if mem[pointer] == 0 => i = partner(code, i),
+            b']' if mem[pointer] != 0 => i = partner(code, i),
+            b'.' => {
+                if written < N {
+                    output[written] = mem[pointer];
+                }
+                written += 1;
+            }
+            b',' => panic!("the code reads input"),
+            _ => {}
+        }
+        i += 1;
+    }
+    (output, written)
+}
+
+/// The number of bytes input-free code writes, for sizing the output of
+/// [`eval`].
+pub const fn output_len(code: &str) -> usize {
+    eval::<0>(code).1
+}
+
+/// The offset of the first command, past a leading `#!` line.
+const fn commands_start(code: &[u8]) -> usize {
+    if code.len() < 2 || code[0] != b'#' || code[1] != b'!' {
+        return 0;
+    }
+    let mut i = 2;
+    while i < code.len() && code[i] != b'\n' {
+        i += 1;
+    }
+    i
+}
+
+/// The offset of the bracket matching the one at `at`, in valid code.
+const fn partner(code: &[u8], at: usize) -> usize {
+    let mut depth = 0isize;
+    let mut i = at;
+    loop {
+        match code[i] {
+            b'[' => depth += 1,
+            b']' => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            return i;
+        }
+        if code[at] == b'[' {
+            i += 1;
+        } else {
+            i -= 1;
+        }
+    }
+}
+
+/// The span of the byte at `offset`, counted as the parser does.
+const fn span_at(code: &[u8], offset: usize) -> Span {
+    let (mut line, mut column) = (1, 1);
+    let mut i = 0;
+    while i < offset {
+        if code[i] == b'\n' {
+            line += 1;
+            column = 1;
+        } else if code[i] & 0xC0 != 0x80 {
+            column += 1;
+        }
+        i += 1;
+    }
+    Span {
+        start: offset,
+        end: offset + 1,
+        line,
+        column,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Buffer, Interpreter, Program, State};
+
+    const HELLO: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+    #[test]
+    fn evaluates_code_at_compile_time() {
+        const OUTPUT: &[u8; 13] = eval_bf!(HELLO);
+        assert_eq!(OUTPUT, b"Hello World!\n");
+        assert_eq!(eval_bf!("#!/bin/bf +.\n-."), &[255]);
+        assert!(eval_bf!("no commands").is_empty());
+    }
+
+    #[test]
+    fn writes_as_much_as_fits() {
+        assert_eq!(eval::<2>("+.+.+."), ([1, 2], 3));
+        assert_eq!(output_len(HELLO), 13);
+    }
+
+    #[test]
+    fn validates_like_the_parser() {
+        for code in ["[]]", "+\n[[]", "[é\n]]", "#![\n+"] {
+            assert_eq!(validate(code), Program::parse(code).map(drop), "{code}");
+        }
+        assert_eq!(validate("[[]]"), Ok(()));
+    }
+
+    #[test]
+    fn writes_what_the_interpreter_writes() {
+        let code = "<++++++++[>++++++++<-]>+.<+++[>+.<-]>[-]-.";
+        let program = Program::parse(code).unwrap();
+        let mut interpreter = Interpreter::with_io(program, State::new(), Buffer::default());
+        interpreter.run().unwrap();
+        let (output, written) = eval::<16>(code);
+        assert_eq!(output[..written], interpreter.io().output);
+    }
+
+    #[cfg(feature = "macros")]
+    #[test]
+    fn parses_code_at_compile_time() {
+        let program = crate::brainfuck!("+ [->+<] comment");
+        assert_eq!(
+            program.ops(),
+            Program::parse("+ [->+<] comment").unwrap().ops()
+        );
+        assert_eq!(
+            program.spans(),
+            Program::parse("+ [->+<] comment").unwrap().spans()
+        );
+        assert_eq!(program.source(), "+ [->+<] comment");
+        assert_eq!(crate::brainfuck!(output: "++++++++[>++++++++<-]>+."), b"A");
+    }
+}
@@ -0,0 +1,110 @@
+use alloc::string::String;
+use alloc::vec;
+#[cfg(feature = "arbitrary")]
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+#[cfg(feature = "arbitrary")]
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::program::{Op, Program};
+#[cfg(feature = "arbitrary")]
+use crate::Instruction;
+
+/// How deeply loops generated by [`Arbitrary`] may be nested.
+#[cfg(feature = "arbitrary")]
+pub const MAX_DEPTH: usize = 16;
+
+#[cfg(feature = "arbitrary")]
+fn instruction(u: &mut Unstructured<'_>, depth: usize) -> Result<Instruction> {
+    let last = if depth < MAX_DEPTH { 6 } else { 5 };
+    Ok(match u.int_in_range(0..=last)? {
+        0 => Instruction::Increment,
+        1 => Instruction::Decrement,
+        2 => Instruction::Forward,
+        3 => Instruction::Backward,
+        4 => Instruction::Out,
+        5 => Instruction::In,
+        _ => Instruction::Loop(instructions(u, depth + 1)?),
+    })
+}
+
+#[cfg(feature = "arbitrary")]
+fn instructions(u: &mut Unstructured<'_>, depth: usize) -> Result<Vec<Instruction>> {
+    let mut instructions = vec![];
+    while u.arbitrary()? {
+        instructions.push(instruction(u, depth)?);
+    }
+    Ok(instructions)
+}
+
+/// Generates any instruction found in a parsed program, so never
+/// [`Instruction::LoopEnd`].
+#[cfg(feature = "arbitrary")]
+impl<'a> Arbitrary<'a> for Instruction {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        instruction(u, 0)
+    }
+}
+
+/// Generates programs with every bracket matched, whose source is only
+/// commands.
+#[cfg(feature = "arbitrary")]
+impl<'a> Arbitrary<'a> for Program {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut source = String::new();
+        for instruction in instructions(u, 0)? {
+            let _ = write!(source, "{instruction}");
+        }
+        Ok(Program::parse(&source).expect("generated brackets are matched"))
+    }
+}
+
+/// The source of a program with only the operations `keep` is true for.
+fn source_of(ops: &[Op], keep: &[bool]) -> String {
+    let mut source = String::new();
+    for (op, _) in ops.iter().zip(keep).filter(|(_, &keep)| keep) {
+        let _ = write!(source, "{op}");
+    }
+    source
+}
+
+impl Program {
+    /// Finds a smaller program for which `fails` is still true, such as
+    /// one which still shows a bug found by fuzzing.
+    ///
+    /// Removes runs of operations, then single operations, for as long as
+    /// `fails` holds. A bracket is always removed along with its partner,
+    /// so removing just a pair of brackets keeps the loop's body.
+    pub fn shrink(&self, mut fails: impl FnMut(&Program) -> bool) -> Program {
+        let mut best = Program::parse(&source_of(self.ops(), &vec![true; self.len()]))
+            .expect("the brackets of a parsed program are matched");
+        loop {
+            let len = best.len();
+            let mut size = len.div_ceil(2);
+            while size > 0 {
+                let mut start = 0;
+                while start < best.len() {
+                    let mut keep = vec![true; best.len()];
+                    for i in start..(start + size).min(best.len()) {
+                        keep[i] = false;
+                        if let Op::Open(partner) | Op::Close(partner) = best.ops()[i] {
+                            keep[partner] = false;
+                        }
+                    }
+                    let candidate = Program::parse(&source_of(best.ops(), &keep))
+                        .expect("brackets are removed in pairs");
+                    if fails(&candidate) {
+                        best = candidate;
+                    } else {
+                        start += size;
+                    }
+                }
+                size /= 2;
+            }
+            if best.len() == len {
+                return best;
+            }
+        }
+    }
+}
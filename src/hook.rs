@@ -0,0 +1,143 @@
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::Write;
+
+use crate::{IoError, Op, State};
+
+/// Something which happened while an [`Interpreter`](crate::Interpreter)
+/// executed an operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// An operation was executed. Every operation produces this event
+    /// before any other.
+    Step {
+        /// The index of the operation in its program.
+        position: usize,
+        /// The operation.
+        op: Op,
+    },
+    /// A byte was written.
+    Output(u8),
+    /// A byte was read, or `None` at the end of input.
+    Input(Option<u8>),
+    /// A `[` was run on a cell which is not zero, entering its loop.
+    LoopEnter {
+        /// The index of the loop's [`Op::Open`].
+        open: usize,
+    },
+    /// A `]` was run on a zero cell, leaving its loop.
+    LoopExit {
+        /// The index of the loop's [`Op::Open`].
+        open: usize,
+    },
+}
+
+/// Watches every operation an [`Interpreter`](crate::Interpreter) executes,
+/// added with [`Interpreter::add_hook`](crate::Interpreter::add_hook).
+pub trait Hook {
+    /// Called after an operation with each event it produced, along with the
+    /// state it left behind.
+    ///
+    /// Returning an error stops the program with [`RuntimeError::Io`](crate::RuntimeError::Io).
+    fn on_event(&mut self, event: &Event, state: &State) -> Result<(), IoError>;
+}
+
+impl<T: Hook + ?Sized> Hook for &mut T {
+    fn on_event(&mut self, event: &Event, state: &State) -> Result<(), IoError> {
+        (**self).on_event(event, state)
+    }
+}
+
+impl<T: Hook + ?Sized> Hook for Box<T> {
+    fn on_event(&mut self, event: &Event, state: &State) -> Result<(), IoError> {
+        (**self).on_event(event, state)
+    }
+}
+
+/// Lets a hook be used after it is added, such as to read what it
+/// collected once the program halts.
+impl<T: Hook + ?Sized> Hook for Rc<RefCell<T>> {
+    fn on_event(&mut self, event: &Event, state: &State) -> Result<(), IoError> {
+        self.borrow_mut().on_event(event, state)
+    }
+}
+
+/// The hooks added to an interpreter.
+#[derive(Default)]
+pub(crate) struct Hooks(pub(crate) Vec<Box<dyn Hook>>);
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Hooks({})", self.0.len())
+    }
+}
+
+impl Hooks {
+    /// Gives each event to every hook, in the order they were added.
+    pub(crate) fn dispatch(&mut self, events: &[Event], state: &State) -> Result<(), IoError> {
+        for hook in &mut self.0 {
+            for event in events {
+                hook.on_event(event, state)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A [`Hook`] writing each event as a line of JSON, for other tools to
+/// analyze runs.
+///
+/// Every object has an `"event"` of `"step"`, `"output"`, `"input"`,
+/// `"loop_enter"` or `"loop_exit"`, and the `"pointer"` and `"cell"` it left
+/// behind. Steps also have the `"step"` number starting at 1, the
+/// `"position"` of the operation, and the `"op"` as its instruction. Input
+/// and output have the `"byte"`, which is `null` at the end of input, and
+/// loop events have the `"position"` of the loop's `[`.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct JsonTracer<W: Write> {
+    out: W,
+    steps: u64,
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> JsonTracer<W> {
+    /// Creates a tracer writing to `out`.
+    pub fn new(out: W) -> Self {
+        Self { out, steps: 0 }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> Hook for JsonTracer<W> {
+    fn on_event(&mut self, event: &Event, state: &State) -> Result<(), IoError> {
+        let out = &mut self.out;
+        match *event {
+            Event::Step { position, op } => {
+                self.steps += 1;
+                write!(
+                    out,
+                    r#"{{"event":"step","step":{},"position":{position},"op":"{op}""#,
+                    self.steps
+                )?;
+            }
+            Event::Output(b) => write!(out, r#"{{"event":"output","byte":{b}"#)?,
+            Event::Input(Some(b)) => write!(out, r#"{{"event":"input","byte":{b}"#)?,
+            Event::Input(None) => write!(out, r#"{{"event":"input","byte":null"#)?,
+            Event::LoopEnter { open } => {
+                write!(out, r#"{{"event":"loop_enter","position":{open}"#)?
+            }
+            Event::LoopExit { open } => write!(out, r#"{{"event":"loop_exit","position":{open}"#)?,
+        }
+        writeln!(
+            out,
+            r#","pointer":{},"cell":{}}}"#,
+            state.pointer,
+            state.cell()
+        )
+    }
+}
@@ -0,0 +1,98 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use crate::{Error, Event, Hook, IoError, State};
+
+/// The number of cells in each row of an image rendered by
+/// [`State::render_image`].
+pub const IMAGE_WIDTH: usize = 256;
+
+impl State {
+    /// Saves the memory as a grayscale PNG, with each cell as a pixel as
+    /// bright as its value, in rows of [`IMAGE_WIDTH`] cells.
+    pub fn render_image(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let width = self.mem.len().clamp(1, IMAGE_WIDTH);
+        let mut pixels = self.mem.clone();
+        pixels.resize(self.mem.len().div_ceil(width).max(1) * width, 0);
+        write_png(path.as_ref(), width, &pixels)
+    }
+}
+
+/// Records the memory as it changes over time, as a [`Hook`], to be saved
+/// as an image with a row for each snapshot.
+///
+/// Add it to an interpreter through an `Rc<RefCell<TapeImage>>` to save it
+/// once the program halts.
+#[derive(Debug, Clone)]
+pub struct TapeImage {
+    rows: Vec<Vec<u8>>,
+    every: u64,
+    steps: u64,
+}
+
+impl TapeImage {
+    /// Creates an image which records the memory every `every` steps.
+    pub fn new(every: u64) -> Self {
+        Self {
+            rows: vec![],
+            every: every.max(1),
+            steps: 0,
+        }
+    }
+
+    /// Adds a row holding the memory of a state.
+    pub fn record(&mut self, state: &State) {
+        self.rows.push(state.mem.clone());
+    }
+
+    /// The number of rows recorded.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Whether no rows have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Saves the rows recorded as a grayscale PNG, from top to bottom, with
+    /// each cell as a pixel as bright as its value.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let width = self.rows.iter().map(Vec::len).max().unwrap_or(0).max(1);
+        let mut pixels = Vec::with_capacity(width * self.rows.len().max(1));
+        for row in &self.rows {
+            pixels.extend(row);
+            pixels.resize(pixels.len() + width - row.len(), 0);
+        }
+        if pixels.is_empty() {
+            pixels.push(0);
+        }
+        write_png(path.as_ref(), width, &pixels)
+    }
+}
+
+impl Hook for TapeImage {
+    fn on_event(&mut self, event: &Event, state: &State) -> Result<(), IoError> {
+        if let Event::Step { .. } = event {
+            self.steps += 1;
+            if self.steps.is_multiple_of(self.every) {
+                self.record(state);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes grayscale pixels, in rows of `width`, to a PNG file.
+fn write_png(path: &Path, width: usize, pixels: &[u8]) -> Result<(), Error> {
+    let too_large = || io::Error::other("the image is too large");
+    let height = u32::try_from(pixels.len() / width).map_err(|_| too_large())?;
+    let width = u32::try_from(width).map_err(|_| too_large())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(pixels).map_err(io::Error::other)?;
+    Ok(writer.finish().map_err(io::Error::other)?)
+}
@@ -1,15 +1,38 @@
-use std::str::Chars;
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+use core::str::Chars;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::{fmt, mem};
 
-use crate::{Instruction, State};
+use crate::analysis::keeps_guard;
+use crate::hook::Hooks;
+#[cfg(feature = "std")]
+use crate::sparse::{self, SparseTape};
+use crate::{
+    Buffer, Config, Coverage, Eof, Event, Hook, Instruction, Io, IoError, Op, Program,
+    RuntimeError, Span, State,
+};
+#[cfg(feature = "std")]
+use crate::{Encoding, Error, OptLevel, StdIo, TapeModel};
 
 /// Parses raw Brainfuck code into list of instructions.
 ///
-/// Ignores characters not in the Brainfuck language.
-pub fn parse(raw: &String) -> Vec<Instruction> {
+/// Ignores characters not in the Brainfuck language, along with a leading
+/// `#!` line.
+pub fn parse(raw: &str) -> Vec<Instruction> {
     let mut instructions = vec![];
-    let mut chars = raw.chars().into_iter();
+    let raw = match raw.strip_prefix("#!") {
+        Some(shebang) => shebang.split_once('\n').map_or("", |(_, rest)| rest),
+        None => raw,
+    };
+    let mut chars = raw.chars();
     while let Some(c) = chars.next() {
-        if let Some(instruction) = Instruction::try_from(c).ok() {
+        if let Ok(instruction) = Instruction::try_from(c) {
             // Iterating through characters now
             if let Instruction::Loop(mut inners) = instruction {
                 parse_loop(&mut chars, &mut inners);
@@ -24,7 +47,7 @@ pub fn parse(raw: &String) -> Vec<Instruction> {
 
 fn parse_loop(chars: &mut Chars, inners: &mut Vec<Instruction>) {
     while let Some(c) = chars.next() {
-        if let Some(instruction) = Instruction::try_from(c).ok() {
+        if let Ok(instruction) = Instruction::try_from(c) {
             match instruction {
                 Instruction::Loop(mut local_inners) => {
                     parse_loop(chars, &mut local_inners);
@@ -40,23 +63,755 @@ fn parse_loop(chars: &mut Chars, inners: &mut Vec<Instruction>) {
 }
 
 /// Executes a list of Brainfuck instructions.
-pub fn execute(state: &mut State, instructions: &Vec<Instruction>) {
+#[cfg(feature = "std")]
+pub fn execute(state: &mut State, instructions: &[Instruction]) -> Result<(), RuntimeError> {
     for instruction in instructions {
-        state.run(instruction);
+        state.run(instruction)?;
     }
+    Ok(())
 }
 
 /// Run Brainfuck code.
-pub fn run(raw: &String) -> State {
+#[cfg(feature = "std")]
+pub fn run(raw: &str) -> Result<State, RuntimeError> {
     let mut state = State::new();
-    run_from_state(raw, &mut state);
-    state
+    run_from_state(raw, &mut state)?;
+    Ok(state)
 }
 
 /// Run Brainfuck code from a previous state.
-pub fn run_from_state(raw: &String, state: &mut State) {
-    let code = parse(raw);
-    for instruction in code {
-        state.run(&instruction)
+#[cfg(feature = "std")]
+pub fn run_from_state(raw: &str, state: &mut State) -> Result<(), RuntimeError> {
+    execute(state, &parse(raw))
+}
+
+/// Run Brainfuck code as chosen by `config`.
+#[cfg(feature = "std")]
+pub fn run_with(raw: &str, config: &Config) -> Result<State, Error> {
+    let mut state = State::new();
+    let program = Program::parse_with(raw, config)?;
+    run_program(program, &mut state, config, config.opt_level)?;
+    Ok(state)
+}
+
+/// Run Brainfuck code from a previous state as chosen by `config`.
+///
+/// As the state may not be new, [`OptLevel::Full`] only optimizes as
+/// much as [`OptLevel::Basic`], and `?` carries on from the state's
+/// generator rather than starting again from [`Config::random`].
+#[cfg(feature = "std")]
+pub fn run_from_state_with(raw: &str, state: &mut State, config: &Config) -> Result<(), Error> {
+    let program = Program::parse_with(raw, config)?;
+    let config = Config {
+        random: None,
+        ..*config
+    };
+    run_program(
+        program,
+        state,
+        &config,
+        config.opt_level.min(OptLevel::Basic),
+    )
+}
+
+#[cfg(feature = "std")]
+fn run_program(
+    program: Program,
+    state: &mut State,
+    config: &Config,
+    opt_level: OptLevel,
+) -> Result<(), Error> {
+    state.ensure_tapes(config.tapes);
+    if let Some(seed) = config.random {
+        state.rng = seed;
+    }
+    if config.tape == TapeModel::Sparse {
+        let mut tape = SparseTape::from_state(state);
+        let mut io = Queued {
+            queue: mem::take(&mut state.queued),
+            io: <StdIo>::default(),
+        };
+        let result = sparse::run(&program, &mut tape, &mut io, config);
+        tape.write_to(state);
+        state.queued = io.queue;
+        return Ok(result.map(drop)?);
+    }
+    if opt_level != OptLevel::None && !config.checks_steps() {
+        let config = Config {
+            opt_level,
+            ..*config
+        };
+        let optimized = program.optimize_with(&config);
+        return Ok(optimized.run(state, &mut <StdIo>::default())?);
+    }
+    let mut interpreter = Interpreter::builder()
+        .io(<StdIo>::default())
+        .state(mem::take(state))
+        .config(config)
+        .build(program);
+    let result = interpreter.run();
+    *state = interpreter.into_state();
+    Ok(result?)
+}
+
+/// Reads the input queued in a state before any from an [`Io`].
+#[cfg(feature = "std")]
+struct Queued<I> {
+    queue: VecDeque<u8>,
+    io: I,
+}
+
+#[cfg(feature = "std")]
+impl<I: Io> Io for Queued<I> {
+    fn read(&mut self) -> Result<Option<u8>, IoError> {
+        match self.queue.pop_front() {
+            Some(b) => Ok(Some(b)),
+            None => self.io.read(),
+        }
+    }
+    fn write(&mut self, byte: u8) -> Result<(), IoError> {
+        self.io.write(byte)
+    }
+    fn flush(&mut self) -> Result<(), IoError> {
+        self.io.flush()
+    }
+}
+
+/// Where a program run by [`run_fragment`] left off.
+#[derive(Debug)]
+pub struct Fragment {
+    /// Which of the bytes written to the state so far were written by the
+    /// program, by their index.
+    pub output: Range<usize>,
+    /// How many bytes the program read, from the state's queued input
+    /// followed by the [`Io`].
+    pub read: usize,
+    /// How many operations the program executed.
+    pub steps: u64,
+    /// Whether the program halted, or why it failed.
+    pub result: Result<(), RuntimeError>,
+}
+
+/// Counts the bytes read through an [`Io`].
+struct Counted<I> {
+    io: I,
+    read: usize,
+}
+
+impl<I: Io> Io for Counted<I> {
+    fn read(&mut self) -> Result<Option<u8>, IoError> {
+        let byte = self.io.read()?;
+        self.read += usize::from(byte.is_some());
+        Ok(byte)
+    }
+    fn write(&mut self, byte: u8) -> Result<(), IoError> {
+        self.io.write(byte)
+    }
+    fn flush(&mut self) -> Result<(), IoError> {
+        self.io.flush()
+    }
+}
+
+/// Runs a program on from where an earlier one left `state`, such as the
+/// next line given to a REPL, as chosen by `config`.
+///
+/// The memory and pointer are kept as they are, so use
+/// [`State::reset_pointer`] or [`State::clear`] first to start over. The
+/// program is never optimized, and `state` is updated even if it fails.
+pub fn run_fragment(program: Program, state: &mut State, io: impl Io, config: &Config) -> Fragment {
+    let written = state.written;
+    let queued = state.queued.len();
+    let mut interpreter = Interpreter::builder()
+        .io(Counted { io, read: 0 })
+        .state(mem::take(state))
+        .config(config)
+        .build(program);
+    let result = interpreter.run();
+    let steps = interpreter.steps();
+    let (new, io) = interpreter.into_parts();
+    *state = new;
+    Fragment {
+        output: written..state.written,
+        read: queued - state.queued.len() + io.read,
+        steps,
+        result,
+    }
+}
+
+/// Runs a program from `state` until it halts, reading and writing through
+/// `io`.
+///
+/// The program is only borrowed and nothing is kept between runs, so one
+/// parsed program can be shared by many threads, each executing it on its
+/// own state. `state` is updated even if the program fails.
+pub fn execute_on(
+    program: &Program,
+    state: &mut State,
+    io: &mut impl Io,
+) -> Result<(), RuntimeError> {
+    let mut interpreter = Interpreter::with_io(program.clone(), mem::take(state), io);
+    let result = interpreter.run();
+    *state = interpreter.into_state();
+    result
+}
+
+/// The loop a program was running when it reached its step limit, given
+/// by [`RuntimeError::StepLimit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Spin {
+    /// The index of the loop's [`Op::Open`].
+    pub open: usize,
+    /// Where the loop is in the source, from its `[` to its `]`.
+    pub span: Span,
+    /// How many times the loop's body was started.
+    pub iterations: u64,
+    /// Whether the loop can never end, as its body always returns to the
+    /// cell it checks without changing it.
+    pub infinite: bool,
+}
+
+impl fmt::Display for Spin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the loop at {}, which ran {} times",
+            self.span, self.iterations
+        )?;
+        if self.infinite {
+            write!(
+                f,
+                " and can never end, as it never changes the cell it checks"
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Steps through a [`Program`] one operation at a time.
+///
+/// Not [`Send`], as it may hold hooks which are not, such as ones shared
+/// through an `Rc<RefCell<_>>`. To run a program on several threads, share
+/// the [`Program`] and give each thread its own interpreter or use
+/// [`execute_on`].
+#[derive(Debug)]
+pub struct Interpreter<I: Io> {
+    program: Program,
+    state: State,
+    io: I,
+    position: usize,
+    steps: u64,
+    max_steps: Option<u64>,
+    max_memory: Option<usize>,
+    strict: bool,
+    eof: Eof,
+    hits: Option<Vec<u64>>,
+    iterations: Vec<u64>,
+    checkpoints: Vec<Snapshot>,
+    hooks: Hooks,
+    cancel: Option<CancelToken>,
+}
+
+/// A point in the execution of a program which an [`Interpreter`] can
+/// return to, created by [`Interpreter::checkpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Checkpoint(usize);
+
+/// A handle which stops an [`Interpreter`] from another thread, created by
+/// [`Interpreter::cancel_token`].
+///
+/// Clones share the same flag, which stays set until it is reset.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Makes the interpreter fail with [`RuntimeError::Cancelled`] before
+    /// its next step.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the token has been cancelled and not reset since.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Lets the interpreter run again after being cancelled.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Everything needed to return to a [`Checkpoint`].
+#[derive(Debug, Clone)]
+struct Snapshot {
+    state: State,
+    position: usize,
+    steps: u64,
+}
+
+/// Assembles the configuration of an [`Interpreter`], created by
+/// [`Interpreter::builder`].
+#[derive(Debug)]
+pub struct InterpreterBuilder<I: Io> {
+    state: State,
+    io: I,
+    tapes: usize,
+    seed: Option<u64>,
+    max_steps: Option<u64>,
+    max_memory: Option<usize>,
+    strict: bool,
+    eof: Eof,
+    coverage: bool,
+    hooks: Hooks,
+}
+
+impl Interpreter<Buffer> {
+    /// Starts configuring an interpreter, which reads from and writes to a
+    /// [`Buffer`] unless given another [`Io`].
+    pub fn builder() -> InterpreterBuilder<Buffer> {
+        InterpreterBuilder {
+            state: State::new(),
+            io: Buffer::default(),
+            tapes: 1,
+            seed: None,
+            max_steps: None,
+            max_memory: None,
+            strict: false,
+            eof: Eof::Unchanged,
+            coverage: false,
+            hooks: Hooks::default(),
+        }
+    }
+}
+
+impl<I: Io> InterpreterBuilder<I> {
+    /// Reads from and writes through `io` instead.
+    pub fn io<J: Io>(self, io: J) -> InterpreterBuilder<J> {
+        InterpreterBuilder {
+            state: self.state,
+            io,
+            tapes: self.tapes,
+            seed: self.seed,
+            max_steps: self.max_steps,
+            max_memory: self.max_memory,
+            strict: self.strict,
+            eof: self.eof,
+            coverage: self.coverage,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Starts from a previous state instead of a new one.
+    pub fn state(mut self, state: State) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// Queues bytes to be read by `,` before any is read from the [`Io`].
+    pub fn input(mut self, input: impl AsRef<[u8]>) -> Self {
+        self.state.queue_input(input);
+        self
+    }
+
+    /// Fails with [`RuntimeError::StepLimit`] once `max_steps` operations
+    /// have been executed.
+    pub fn max_steps(mut self, max_steps: u64) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    /// Fails with [`RuntimeError::MemoryLimit`] if the program moves past
+    /// the first `max_memory` cells.
+    pub fn max_memory(mut self, max_memory: usize) -> Self {
+        self.max_memory = Some(max_memory);
+        self
+    }
+
+    /// Sets whether moving left of the first cell or overflowing a cell
+    /// fails, instead of being ignored or wrapping around.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets what `,` does once there is no more input.
+    pub fn eof(mut self, eof: Eof) -> Self {
+        self.eof = eof;
+        self
+    }
+
+    /// Applies the limits, strict mode, and end of input behavior chosen by
+    /// `config`.
+    pub fn config(mut self, config: &Config) -> Self {
+        self.tapes = config.tapes;
+        self.seed = config.random;
+        self.max_steps = config.max_steps;
+        self.max_memory = config.max_memory;
+        self.strict = config.strict;
+        self.eof = config.eof;
+        self
+    }
+
+    /// Counts how many times each operation is executed.
+    pub fn coverage(mut self) -> Self {
+        self.coverage = true;
+        self
+    }
+
+    /// Adds a hook which is given the events produced by each operation.
+    pub fn hook(mut self, hook: impl Hook + 'static) -> Self {
+        self.hooks.0.push(Box::new(hook));
+        self
+    }
+
+    /// Creates the interpreter for a program.
+    pub fn build(mut self, program: Program) -> Interpreter<I> {
+        self.state.ensure_tapes(self.tapes);
+        if let Some(seed) = self.seed {
+            self.state.rng = seed;
+        }
+        let mut interpreter = Interpreter::with_io(program, self.state, self.io);
+        interpreter.max_steps = self.max_steps;
+        interpreter.max_memory = self.max_memory;
+        interpreter.strict = self.strict;
+        interpreter.eof = self.eof;
+        interpreter.hooks = self.hooks;
+        if self.coverage {
+            interpreter.track_coverage();
+        }
+        interpreter
+    }
+}
+
+#[cfg(feature = "std")]
+impl Interpreter<StdIo> {
+    /// Creates an interpreter for a program, starting with a new state.
+    pub fn new(program: Program) -> Self {
+        Self::from_state(program, State::new())
+    }
+
+    /// Creates an interpreter for a program, starting from a previous state.
+    pub fn from_state(program: Program, state: State) -> Self {
+        Interpreter::with_io(program, state, StdIo::default())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: Encoding> Interpreter<StdIo<E>> {
+    /// Creates an interpreter for a program which reads from stdin and
+    /// writes to stdout, translating through `encoding`.
+    pub fn with_encoding(program: Program, state: State, encoding: E) -> Self {
+        Interpreter::with_io(program, state, StdIo::new(encoding))
+    }
+}
+
+impl<I: Io> Interpreter<I> {
+    /// Creates an interpreter for a program which reads and writes through `io`.
+    pub fn with_io(program: Program, state: State, io: I) -> Self {
+        let len = program.len();
+        Self {
+            program,
+            state,
+            io,
+            position: 0,
+            steps: 0,
+            max_steps: None,
+            max_memory: None,
+            strict: false,
+            eof: Eof::Unchanged,
+            hits: None,
+            iterations: vec![0; len],
+            checkpoints: vec![],
+            hooks: Hooks::default(),
+            cancel: None,
+        }
+    }
+
+    /// Fails with [`RuntimeError::StepLimit`] once `max_steps` operations
+    /// have been executed.
+    pub fn limit_steps(&mut self, max_steps: u64) {
+        self.max_steps = Some(max_steps);
+    }
+
+    /// Fails with [`RuntimeError::MemoryLimit`] if the program moves past
+    /// the first `max_memory` cells.
+    pub fn limit_memory(&mut self, max_memory: usize) {
+        self.max_memory = Some(max_memory);
+    }
+
+    /// Sets whether moving left of the first cell or overflowing a cell
+    /// fails, instead of being ignored or wrapping around.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Sets what `,` does once there is no more input.
+    pub fn set_eof(&mut self, eof: Eof) {
+        self.eof = eof;
+    }
+
+    /// A handle which makes the interpreter fail with
+    /// [`RuntimeError::Cancelled`] before its next step once cancelled,
+    /// even from another thread.
+    pub fn cancel_token(&mut self) -> CancelToken {
+        self.cancel.get_or_insert_with(CancelToken::default).clone()
+    }
+
+    /// Adds a hook which is given the events produced by each operation.
+    pub fn add_hook(&mut self, hook: impl Hook + 'static) {
+        self.hooks.0.push(Box::new(hook));
+    }
+
+    /// Starts counting how many times each operation is executed.
+    pub fn track_coverage(&mut self) {
+        self.hits = Some(vec![0; self.program.len()]);
+    }
+
+    /// The coverage collected so far, if [`track_coverage`](Self::track_coverage)
+    /// was called.
+    pub fn coverage(&self) -> Option<Coverage> {
+        let hits = self.hits.clone()?;
+        Some(Coverage::new(self.program.spans().to_vec(), hits))
+    }
+
+    /// How many times the body of the loop whose [`Op::Open`] is at `open`
+    /// has been started.
+    pub fn iterations(&self, open: usize) -> u64 {
+        self.iterations.get(open).copied().unwrap_or(0)
+    }
+
+    /// The loop the next operation is in, preferring an enclosing loop which
+    /// can never end over the innermost one.
+    pub fn spinning(&self) -> Option<Spin> {
+        let ops = self.program.ops();
+        let mut loops = vec![];
+        let mut depth = 0usize;
+        if let Some(&Op::Close(open)) = ops.get(self.position) {
+            loops.push(open);
+        }
+        for i in (0..self.position.min(ops.len())).rev() {
+            match ops[i] {
+                Op::Close(_) => depth += 1,
+                Op::Open(_) if depth > 0 => depth -= 1,
+                Op::Open(_) => loops.push(i),
+                _ => {}
+            }
+        }
+        let open = loops
+            .iter()
+            .copied()
+            .find(|&open| keeps_guard(ops, open))
+            .or(loops.first().copied())?;
+        let Op::Open(close) = ops[open] else {
+            unreachable!("loops start at an open bracket")
+        };
+        Some(Spin {
+            open,
+            span: self.program.spans()[open].to(self.program.spans()[close]),
+            iterations: self.iterations[open],
+            infinite: keeps_guard(ops, open),
+        })
+    }
+
+    /// The program being interpreted.
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// The state of the process.
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /// The state of the process, mutably.
+    pub fn state_mut(&mut self) -> &mut State {
+        &mut self.state
+    }
+
+    /// The input and output of the process.
+    pub fn io(&self) -> &I {
+        &self.io
+    }
+
+    /// The input and output of the process, mutably.
+    pub fn io_mut(&mut self) -> &mut I {
+        &mut self.io
+    }
+
+    /// Consumes the interpreter, returning the state of the process.
+    pub fn into_state(self) -> State {
+        self.state
+    }
+
+    /// Consumes the interpreter, returning the state and the input and output
+    /// of the process.
+    pub fn into_parts(self) -> (State, I) {
+        (self.state, self.io)
+    }
+
+    /// The index of the next operation to execute.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The number of operations executed so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Captures the state and position of the process, so it can be returned
+    /// to with [`rollback`](Self::rollback).
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        self.checkpoints.push(Snapshot {
+            state: self.state.clone(),
+            position: self.position,
+            steps: self.steps,
+        });
+        Checkpoint(self.checkpoints.len() - 1)
+    }
+
+    /// Returns the process to the state and position it had at `checkpoint`.
+    ///
+    /// The checkpoint is kept, so it can be returned to again. Output already
+    /// written and input already read from the [`Io`] are not undone.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the checkpoint was not created by this interpreter.
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        let snapshot = self.checkpoints[checkpoint.0].clone();
+        self.state = snapshot.state;
+        self.position = snapshot.position;
+        self.steps = snapshot.steps;
+    }
+
+    /// Forgets every checkpoint, freeing the memory they hold.
+    pub fn clear_checkpoints(&mut self) {
+        self.checkpoints.clear();
+    }
+
+    /// Whether every operation has been executed.
+    pub fn is_halted(&self) -> bool {
+        self.position >= self.program.len()
+    }
+
+    /// Executes the next operation, if the program has not halted.
+    ///
+    /// If the operation fails, the interpreter stays on it. If a hook fails,
+    /// the operation has still been executed.
+    pub fn step(&mut self) -> Result<(), RuntimeError> {
+        let Some(&op) = self.program.ops().get(self.position) else {
+            return Ok(());
+        };
+        if let Some(max_steps) = self.max_steps {
+            if self.steps >= max_steps {
+                return Err(RuntimeError::StepLimit(max_steps, self.spinning()));
+            }
+        }
+        if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+            return Err(RuntimeError::Cancelled);
+        }
+        let mut next = self.position + 1;
+        let mut event = None;
+        match op {
+            Op::Increment => {
+                if self.strict && self.state.cell() == u8::MAX {
+                    return Err(RuntimeError::CellOverflow);
+                }
+                self.state.increment()
+            }
+            Op::Decrement => {
+                if self.strict && self.state.cell() == 0 {
+                    return Err(RuntimeError::CellOverflow);
+                }
+                self.state.decrement()
+            }
+            Op::Forward => {
+                if let Some(max_memory) = self.max_memory {
+                    if self.state.pointer + 1 >= max_memory {
+                        return Err(RuntimeError::MemoryLimit(max_memory));
+                    }
+                }
+                self.state.forward()
+            }
+            Op::Backward => {
+                if self.strict && self.state.pointer == 0 {
+                    return Err(RuntimeError::PointerUnderflow);
+                }
+                self.state.backward()
+            }
+            Op::Open(close) => {
+                if self.state.cell() == 0 {
+                    next = close + 1;
+                } else {
+                    self.iterations[self.position] += 1;
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(
+                        open = self.position,
+                        pointer = self.state.pointer,
+                        "entering loop"
+                    );
+                    event = Some(Event::LoopEnter {
+                        open: self.position,
+                    });
+                }
+            }
+            Op::Close(open) => {
+                if self.state.cell() != 0 {
+                    next = open + 1;
+                    self.iterations[open] += 1;
+                } else {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(open, pointer = self.state.pointer, "leaving loop");
+                    event = Some(Event::LoopExit { open });
+                }
+            }
+            Op::Out => {
+                self.state.output(&mut self.io)?;
+                event = Some(Event::Output(self.state.cell()));
+            }
+            Op::In => {
+                let read = self.state.input(&mut self.io)?;
+                if read.is_none() {
+                    let cell = self.state.cell_mut();
+                    *cell = self.eof.value(*cell);
+                }
+                event = Some(Event::Input(read));
+            }
+            Op::PrevTape => self.state.prev_tape(),
+            Op::NextTape => self.state.next_tape(),
+            Op::Random => *self.state.cell_mut() = self.state.random(),
+        }
+        if let Some(hits) = &mut self.hits {
+            hits[self.position] += 1;
+        }
+        let position = self.position;
+        self.steps += 1;
+        self.position = next;
+        if !self.hooks.0.is_empty() {
+            let step = Event::Step { position, op };
+            match event {
+                Some(event) => self.hooks.dispatch(&[step, event], &self.state)?,
+                None => self.hooks.dispatch(&[step], &self.state)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes the program until it halts.
+    pub fn run(&mut self) -> Result<(), RuntimeError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("run", ops = self.program.len()).entered();
+        while !self.is_halted() {
+            let result = self.step();
+            #[cfg(feature = "tracing")]
+            if let Err(e) = &result {
+                tracing::debug!(steps = self.steps, error = %e, "failed");
+            }
+            result?;
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(steps = self.steps, "halted");
+        Ok(self.io.flush()?)
     }
 }
@@ -0,0 +1,534 @@
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{stdin, stdout, BufRead, Read, Write};
+#[cfg(feature = "std")]
+use std::sync::{Mutex, PoisonError};
+
+#[cfg(feature = "std")]
+use crate::compat::{Encoding, Iso8859_1};
+use crate::IoError;
+
+/// Where a Brainfuck process reads its input from and writes its output to.
+pub trait Io {
+    /// Reads a byte for the `,` instruction.
+    ///
+    /// Returns `None` once there is no more input.
+    fn read(&mut self) -> Result<Option<u8>, IoError>;
+    /// Writes a byte for the `.` instruction.
+    fn write(&mut self, byte: u8) -> Result<(), IoError>;
+    /// Finishes writing any output held back, once the program halts.
+    fn flush(&mut self) -> Result<(), IoError> {
+        Ok(())
+    }
+}
+
+impl<T: Io + ?Sized> Io for &mut T {
+    fn read(&mut self) -> Result<Option<u8>, IoError> {
+        (**self).read()
+    }
+    fn write(&mut self, byte: u8) -> Result<(), IoError> {
+        (**self).write(byte)
+    }
+    fn flush(&mut self) -> Result<(), IoError> {
+        (**self).flush()
+    }
+}
+
+impl<T: Io + ?Sized> Io for Box<T> {
+    fn read(&mut self) -> Result<Option<u8>, IoError> {
+        (**self).read()
+    }
+    fn write(&mut self, byte: u8) -> Result<(), IoError> {
+        (**self).write(byte)
+    }
+    fn flush(&mut self) -> Result<(), IoError> {
+        (**self).flush()
+    }
+}
+
+/// When output written to stdout is flushed, so it appears.
+///
+/// Output is also flushed before reading input, so prompts are shown, and
+/// once the program halts.
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Flush {
+    /// After every byte, for interactive programs.
+    #[default]
+    Byte,
+    /// After every line feed.
+    Line,
+    /// Only once the program halts, or a large amount of output is held
+    /// back, for programs which write a lot of output.
+    Halt,
+}
+
+/// What invalid UTF-8 is written as.
+#[cfg(feature = "std")]
+const REPLACEMENT: &[u8] = "\u{FFFD}".as_bytes();
+
+/// The most output [`Flush::Halt`] holds back before writing it anyway.
+#[cfg(feature = "std")]
+const HELD_BACK: usize = 64 * 1024;
+
+/// Output on its way to stdout, flushed as chosen by a [`Flush`].
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone)]
+struct Stdout {
+    flush: Flush,
+    held: Vec<u8>,
+}
+
+#[cfg(feature = "std")]
+impl Stdout {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), IoError> {
+        self.held.extend_from_slice(bytes);
+        let due = match self.flush {
+            Flush::Byte => true,
+            Flush::Line => bytes.contains(&b'\n'),
+            Flush::Halt => self.held.len() >= HELD_BACK,
+        };
+        if due {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        let mut stdout = stdout().lock();
+        stdout.write_all(&self.held)?;
+        self.held.clear();
+        stdout.flush()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for Stdout {
+    fn drop(&mut self) {
+        // Output held back when a program fails is still written.
+        let _ = self.flush();
+    }
+}
+
+/// The rest of the line last read from stdin.
+///
+/// It is shared by every `Io` reading stdin, so none of a line is lost
+/// when one is dropped for another.
+#[cfg(feature = "std")]
+static LINE: Mutex<VecDeque<u8>> = Mutex::new(VecDeque::new());
+
+/// Input read from stdin a line at a time, so every byte of a line typed
+/// into a terminal is read by the `,` instructions which follow.
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, Copy)]
+struct Stdin;
+
+#[cfg(feature = "std")]
+impl Stdin {
+    fn byte(&mut self) -> Result<Option<u8>, IoError> {
+        let mut buf = [0];
+        Ok(match self.read(&mut buf)? {
+            0 => None,
+            _ => Some(buf[0]),
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl Read for Stdin {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        let mut queued = LINE.lock().unwrap_or_else(PoisonError::into_inner);
+        if queued.is_empty() {
+            let mut line = vec![];
+            stdin().lock().read_until(b'\n', &mut line)?;
+            queued.extend(line);
+        }
+        queued.read(buf)
+    }
+}
+
+/// Reads from stdin and writes to stdout, translating between bytes and
+/// the terminal's UTF-8 text through an [`Encoding`].
+///
+/// Input is read a line at a time, and each `,` reads the next byte of it.
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone)]
+pub struct StdIo<E: Encoding = Iso8859_1> {
+    encoding: E,
+    out: Stdout,
+}
+
+#[cfg(feature = "std")]
+impl<E: Encoding> StdIo<E> {
+    /// Creates an `Io` for stdin and stdout which translates through `encoding`.
+    pub fn new(encoding: E) -> Self {
+        Self {
+            encoding,
+            out: Stdout::default(),
+        }
+    }
+
+    /// Flushes output as chosen by `flush`, instead of after every byte.
+    pub fn with_flush(mut self, flush: Flush) -> Self {
+        self.out.flush = flush;
+        self
+    }
+
+    /// The encoding bytes are translated through.
+    pub fn encoding(&self) -> &E {
+        &self.encoding
+    }
+}
+
+/// Reads a single UTF-8 character.
+///
+/// Bytes which are not valid UTF-8 are read as ISO-8859-1.
+#[cfg(feature = "std")]
+fn read_char(reader: &mut impl Read) -> Result<Option<char>, IoError> {
+    let mut buf = [0; 4];
+    if reader.read(&mut buf[..1])? == 0 {
+        return Ok(None);
+    }
+    let len = match buf[0] {
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF7 => 4,
+        _ => 1,
+    };
+    for i in 1..len {
+        if reader.read(&mut buf[i..i + 1])? == 0 {
+            break;
+        }
+    }
+    Ok(Some(match std::str::from_utf8(&buf[..len]) {
+        Ok(s) => s.chars().next().unwrap_or_default(),
+        Err(_) => buf[0] as char,
+    }))
+}
+
+#[cfg(feature = "std")]
+impl<E: Encoding> Io for StdIo<E> {
+    fn read(&mut self) -> Result<Option<u8>, IoError> {
+        self.out.flush()?;
+        Ok(read_char(&mut Stdin)?.map(|c| self.encoding.encode(c).unwrap_or_default()))
+    }
+    fn write(&mut self, byte: u8) -> Result<(), IoError> {
+        let mut buf = [0; 4];
+        self.out
+            .write(self.encoding.decode(byte).encode_utf8(&mut buf).as_bytes())
+    }
+    fn flush(&mut self) -> Result<(), IoError> {
+        self.out.flush()
+    }
+}
+
+/// Reads single keystrokes from the terminal as soon as they are pressed,
+/// and writes to stdout like [`StdIo`].
+///
+/// The terminal is only put into raw mode while waiting for a key, so
+/// output is laid out as usual. Enter reads as a line feed, Ctrl-D as the
+/// end of input, and Ctrl-C fails with [`std::io::ErrorKind::Interrupted`].
+#[cfg(feature = "terminal")]
+#[derive(Debug, Default, Clone)]
+pub struct TerminalIo<E: Encoding = Iso8859_1> {
+    out: StdIo<E>,
+}
+
+#[cfg(feature = "terminal")]
+impl<E: Encoding> TerminalIo<E> {
+    /// Creates an `Io` for the terminal which translates through `encoding`.
+    pub fn new(encoding: E) -> Self {
+        Self {
+            out: StdIo::new(encoding),
+        }
+    }
+
+    /// Waits for a key which can be read as a byte.
+    fn read_key(&mut self) -> Result<Option<u8>, IoError> {
+        use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+
+        loop {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let c = match key.code {
+                KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => match c {
+                    'c' => return Err(std::io::ErrorKind::Interrupted.into()),
+                    'd' => return Ok(None),
+                    'a'..='z' => return Ok(Some(c as u8 & 0x1F)),
+                    _ => continue,
+                },
+                KeyCode::Char(c) => c,
+                KeyCode::Enter => '\n',
+                KeyCode::Tab => '\t',
+                KeyCode::Backspace => '\u{8}',
+                KeyCode::Esc => '\u{1b}',
+                _ => continue,
+            };
+            if let Some(b) = self.out.encoding().encode(c) {
+                return Ok(Some(b));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "terminal")]
+impl<E: Encoding> Io for TerminalIo<E> {
+    fn read(&mut self) -> Result<Option<u8>, IoError> {
+        use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+
+        enable_raw_mode()?;
+        let result = self.read_key();
+        disable_raw_mode()?;
+        result
+    }
+    fn write(&mut self, byte: u8) -> Result<(), IoError> {
+        self.out.write(byte)
+    }
+    fn flush(&mut self) -> Result<(), IoError> {
+        self.out.flush()
+    }
+}
+
+/// Reads from stdin and writes to stdout byte for byte, without any
+/// translation.
+///
+/// Most Brainfuck programs expect this, especially those handling binary data.
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone)]
+pub struct RawStdIo {
+    out: Stdout,
+}
+
+#[cfg(feature = "std")]
+impl RawStdIo {
+    /// Creates an `Io` for stdin and stdout which flushes output as chosen
+    /// by `flush`.
+    pub fn with_flush(flush: Flush) -> Self {
+        Self {
+            out: Stdout {
+                flush,
+                held: vec![],
+            },
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Io for RawStdIo {
+    fn read(&mut self) -> Result<Option<u8>, IoError> {
+        self.out.flush()?;
+        Stdin.byte()
+    }
+    fn write(&mut self, byte: u8) -> Result<(), IoError> {
+        self.out.write(&[byte])
+    }
+    fn flush(&mut self) -> Result<(), IoError> {
+        self.out.flush()
+    }
+}
+
+/// Reads from any reader and writes to any writer, such as files.
+///
+/// Created with [`new`](Self::new), bytes are translated to and from UTF-8
+/// text through an [`Encoding`] like [`StdIo`]. Created with
+/// [`raw`](Self::raw), they are read and written as they are. Output is only
+/// flushed once the program halts, so wrap slow writers in a
+/// [`BufWriter`](std::io::BufWriter).
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone)]
+pub struct Streams<R, W, E = Iso8859_1> {
+    input: R,
+    output: W,
+    encoding: Option<E>,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read, W: Write> Streams<R, W> {
+    /// Creates an `Io` which reads and writes bytes without translating them.
+    pub fn raw(input: R, output: W) -> Self {
+        Self {
+            input,
+            output,
+            encoding: None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read, W: Write, E: Encoding> Streams<R, W, E> {
+    /// Creates an `Io` which reads and writes text translated through
+    /// `encoding`.
+    pub fn new(input: R, output: W, encoding: E) -> Self {
+        Self {
+            input,
+            output,
+            encoding: Some(encoding),
+        }
+    }
+
+    /// Gives back the reader and writer.
+    pub fn into_inner(self) -> (R, W) {
+        (self.input, self.output)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read, W: Write, E: Encoding> Io for Streams<R, W, E> {
+    fn read(&mut self) -> Result<Option<u8>, IoError> {
+        let Some(encoding) = &self.encoding else {
+            let mut buf = [0];
+            return Ok(match self.input.read(&mut buf)? {
+                0 => None,
+                _ => Some(buf[0]),
+            });
+        };
+        Ok(read_char(&mut self.input)?.map(|c| encoding.encode(c).unwrap_or_default()))
+    }
+    fn write(&mut self, byte: u8) -> Result<(), IoError> {
+        match &self.encoding {
+            Some(encoding) => write!(self.output, "{}", encoding.decode(byte)),
+            None => self.output.write_all(&[byte]),
+        }
+    }
+    fn flush(&mut self) -> Result<(), IoError> {
+        self.output.flush()
+    }
+}
+
+/// Reads bytes from stdin and writes to stdout, decoding the output as UTF-8.
+///
+/// Bytes are held back until they form a whole character, so programs
+/// writing multi-byte text display correctly. Invalid sequences are
+/// written as `U+FFFD REPLACEMENT CHARACTER`.
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone)]
+pub struct Utf8StdIo {
+    pending: Vec<u8>,
+    out: Stdout,
+}
+
+#[cfg(feature = "std")]
+impl Utf8StdIo {
+    /// Creates an `Io` for stdin and stdout which flushes output as chosen
+    /// by `flush`.
+    pub fn with_flush(flush: Flush) -> Self {
+        Self {
+            pending: vec![],
+            out: Stdout {
+                flush,
+                held: vec![],
+            },
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Io for Utf8StdIo {
+    fn read(&mut self) -> Result<Option<u8>, IoError> {
+        self.out.flush()?;
+        Stdin.byte()
+    }
+    fn write(&mut self, byte: u8) -> Result<(), IoError> {
+        self.pending.push(byte);
+        loop {
+            match std::str::from_utf8(&self.pending) {
+                Ok(s) => {
+                    self.out.write(s.as_bytes())?;
+                    self.pending.clear();
+                    break;
+                }
+                // The character is not finished yet.
+                Err(e) if e.error_len().is_none() => {
+                    let valid = e.valid_up_to();
+                    self.out.write(&self.pending[..valid])?;
+                    self.pending.drain(..valid);
+                    break;
+                }
+                Err(e) => {
+                    let invalid = e.valid_up_to() + e.error_len().unwrap_or(1);
+                    self.out.write(&self.pending[..e.valid_up_to()])?;
+                    self.out.write(REPLACEMENT)?;
+                    self.pending.drain(..invalid);
+                }
+            }
+        }
+        Ok(())
+    }
+    fn flush(&mut self) -> Result<(), IoError> {
+        if !self.pending.is_empty() {
+            self.pending.clear();
+            self.out.write(REPLACEMENT)?;
+        }
+        self.out.flush()
+    }
+}
+
+/// Reads from and writes to memory, without any translation.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Buffer {
+    /// The bytes left to be read.
+    pub input: VecDeque<u8>,
+    /// The bytes written so far.
+    pub output: Vec<u8>,
+}
+
+impl Buffer {
+    /// Creates a buffer which will be read from in order.
+    pub fn new(input: impl Into<VecDeque<u8>>) -> Self {
+        Self {
+            input: input.into(),
+            output: vec![],
+        }
+    }
+}
+
+impl Io for Buffer {
+    fn read(&mut self) -> Result<Option<u8>, IoError> {
+        Ok(self.input.pop_front())
+    }
+    fn write(&mut self, byte: u8) -> Result<(), IoError> {
+        self.output.push(byte);
+        Ok(())
+    }
+}
+
+/// Reads and writes by calling a pair of closures.
+///
+/// This is the simplest way to connect a process to hardware without `std`.
+#[derive(Debug, Clone, Copy)]
+pub struct FnIo<R, W> {
+    read: R,
+    write: W,
+}
+
+impl<R, W> FnIo<R, W>
+where
+    R: FnMut() -> Option<u8>,
+    W: FnMut(u8),
+{
+    /// Creates an `Io` which calls `read` for each `,` and `write` for each `.`.
+    pub fn new(read: R, write: W) -> Self {
+        Self { read, write }
+    }
+}
+
+impl<R, W> Io for FnIo<R, W>
+where
+    R: FnMut() -> Option<u8>,
+    W: FnMut(u8),
+{
+    fn read(&mut self) -> Result<Option<u8>, IoError> {
+        Ok((self.read)())
+    }
+    fn write(&mut self, byte: u8) -> Result<(), IoError> {
+        (self.write)(byte);
+        Ok(())
+    }
+}
@@ -1,12 +1,107 @@
-use std::io::{stdin, stdout, Read, Write};
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+// The code `brainfuck!` expands to names this crate, even in its own tests.
+#[cfg(all(test, feature = "macros"))]
+extern crate self as headaches;
+
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::{fmt, mem};
+
+pub mod advise;
+pub mod analysis;
+pub mod asm;
+#[cfg(feature = "std")]
+pub mod batch;
 pub mod compat;
+pub mod config;
+pub mod coverage;
+#[cfg(feature = "json")]
+pub mod dap;
+pub mod debug;
+pub mod diff;
+pub mod error;
+pub mod eval;
+#[cfg(feature = "json")]
+mod framing;
+pub mod fuzz;
+pub mod hook;
+#[cfg(feature = "image")]
+pub mod image;
 pub mod interpret;
+pub mod io;
+pub mod lint;
+#[cfg(feature = "json")]
+pub mod lsp;
+#[cfg(feature = "std")]
+pub mod meter;
+pub mod optimize;
+pub mod program;
+#[cfg(feature = "python")]
+mod python;
+pub mod record;
+#[cfg(feature = "std")]
+pub mod sandbox;
+pub mod sparse;
+#[cfg(feature = "std")]
+pub mod testing;
+pub mod transpile;
+#[cfg(feature = "tui")]
+pub mod watch;
 
-use compat::from_char_8859;
-pub use interpret::{execute, parse, run, run_from_state};
+pub use config::{Config, Eof, OptLevel, Profile, TapeModel};
+pub use coverage::Coverage;
+pub use debug::{Breakpoint, Condition, Debugger, Edit, Stop};
+pub use diff::{CellChange, StateDiff};
+pub use error::{AsmError, Chain, Error, IoError, LogError, ParseError, RuntimeError, TableError};
+#[cfg(feature = "std")]
+pub use hook::JsonTracer;
+pub use hook::{Event, Hook};
+#[cfg(feature = "image")]
+pub use image::TapeImage;
+#[cfg(feature = "std")]
+pub use interpret::{execute, run, run_from_state, run_from_state_with, run_with};
+pub use interpret::{
+    execute_on, parse, run_fragment, CancelToken, Checkpoint, Fragment, Interpreter,
+    InterpreterBuilder, Spin,
+};
+#[cfg(feature = "terminal")]
+pub use io::TerminalIo;
+pub use io::{Buffer, FnIo, Io};
+#[cfg(feature = "std")]
+pub use io::{Flush, RawStdIo, StdIo, Streams, Utf8StdIo};
 
-pub use compat::to_char_8859;
+pub use compat::{
+    to_char_8859, try_from_char_8859, Controls, Cp437, Encoding, FilterControls, Iso8859_1, Table,
+    Windows1252,
+};
+#[cfg(feature = "macros")]
+pub use headaches_macros::brainfuck;
+pub use lint::{Diagnostic, Lint};
+#[cfg(feature = "std")]
+pub use meter::MeterReport;
+pub use optimize::{Ir, LoopKind, Mapping, Optimized, SourceMap};
+pub use program::{Op, Program, Span};
+#[cfg(feature = "std")]
+pub use record::Record;
+pub use record::Replay;
+#[cfg(feature = "tui")]
+pub use watch::watch;
+
+// Parsed programs and their states can be moved and shared between
+// threads.
+const _: () = {
+    const fn shareable<T: Send + Sync>() {}
+    shareable::<Program>();
+    shareable::<Optimized>();
+    shareable::<State>();
+    shareable::<Config>();
+    shareable::<Buffer>();
+    shareable::<Coverage>();
+    shareable::<Error>();
+};
 
 /// A `Vec` of `u8`s representing a the memory
 /// of a Brainfuck process.
@@ -15,8 +110,25 @@ pub type Memory = Vec<u8>;
 /// of a Brainfuck process.
 pub type Pointer = usize;
 
+/// The number of cells in the memory of the original Brainfuck
+/// implementation.
+pub const CLASSIC_CELLS: usize = 30_000;
+
+/// The fewest cells the memory grows by when the pointer moves past its end,
+/// so sweeping right does not reallocate at every step.
+const GROWTH: usize = 1024;
+
+/// Generates a pseudo-random byte with SplitMix64, advancing `rng`.
+pub(crate) fn random(rng: &mut u64) -> u8 {
+    *rng = rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *rng;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    ((z ^ (z >> 31)) >> 56) as u8
+}
+
 /// The state of a Brainfuck process.
-#[derive(Debug)]
+#[derive(Debug, Default, Clone)]
 pub struct State {
     /// The process's memory.
     pub mem: Memory,
@@ -26,6 +138,19 @@ pub struct State {
     ///
     /// Used for repl
     pub outted: bool,
+    /// Input waiting to be read by `,` before any is read from the process's
+    /// [`Io`].
+    pub queued: VecDeque<u8>,
+    /// The number of bytes the process has written.
+    pub written: usize,
+    /// The memory and pointer of every tape, if there are several. The
+    /// selected tape's are moved into [`mem`](Self::mem) and
+    /// [`pointer`](Self::pointer), leaving its bank empty.
+    pub banks: Vec<(Memory, Pointer)>,
+    /// The index of the selected tape.
+    pub bank: usize,
+    /// The state of the generator `?` reads pseudo-random bytes from.
+    pub rng: u64,
 }
 
 impl State {
@@ -35,50 +160,174 @@ impl State {
             mem: vec![0],
             pointer: 0,
             outted: false,
+            queued: VecDeque::new(),
+            written: 0,
+            banks: vec![],
+            bank: 0,
+            rng: 0,
+        }
+    }
+    /// Creates a new Brainfuck state with `tapes` tapes.
+    pub fn with_tapes(tapes: usize) -> Self {
+        let mut state = Self::new();
+        state.ensure_tapes(tapes);
+        state
+    }
+    /// Adds tapes, each with one zero cell, until there are at least `tapes`.
+    pub fn ensure_tapes(&mut self, tapes: usize) {
+        if tapes <= self.tapes() {
+            return;
+        }
+        if self.banks.is_empty() {
+            self.banks.push((vec![], 0));
+        }
+        while self.banks.len() < tapes {
+            self.banks.push((vec![0], 0));
+        }
+    }
+    /// The number of tapes.
+    pub fn tapes(&self) -> usize {
+        self.banks.len().max(1)
+    }
+    /// Selects a tape, wrapping around past the last one.
+    pub fn select_tape(&mut self, tape: usize) {
+        let tape = tape % self.tapes();
+        if tape == self.bank {
+            return;
+        }
+        self.banks[self.bank] = (mem::take(&mut self.mem), self.pointer);
+        (self.mem, self.pointer) = mem::take(&mut self.banks[tape]);
+        self.bank = tape;
+    }
+    /// Selects the tape before the selected one, or the last one.
+    pub fn prev_tape(&mut self) {
+        self.select_tape(self.bank + self.tapes() - 1);
+    }
+    /// Selects the tape after the selected one, or the first one.
+    pub fn next_tape(&mut self) {
+        self.select_tape(self.bank + 1);
+    }
+    /// Generates the next pseudo-random byte.
+    pub fn random(&mut self) -> u8 {
+        random(&mut self.rng)
+    }
+    /// Creates a new Brainfuck state with room for `cells` cells, so the
+    /// memory does not need to grow until the pointer moves past them.
+    ///
+    /// Use [`CLASSIC_CELLS`] for the memory of the original implementation.
+    pub fn with_capacity(cells: usize) -> Self {
+        let mut state = Self::new();
+        state.mem.reserve(cells.saturating_sub(state.mem.len()));
+        state
+    }
+    /// Queues bytes to be read by `,` before any is read from the
+    /// process's [`Io`].
+    pub fn queue_input(&mut self, bytes: impl AsRef<[u8]>) {
+        self.queued.extend(bytes.as_ref());
+    }
+    /// The value of the selected cell.
+    pub fn cell(&self) -> u8 {
+        self.mem.get(self.pointer).copied().unwrap_or(0)
+    }
+    /// The selected cell, growing the memory to reach it if needed.
+    pub fn cell_mut(&mut self) -> &mut u8 {
+        if self.pointer >= self.mem.len() {
+            self.grow();
         }
+        &mut self.mem[self.pointer]
+    }
+    /// Grows the memory to reach the pointer, making room for at least
+    /// [`GROWTH`] more cells at once.
+    #[cold]
+    fn grow(&mut self) {
+        let needed = self.pointer + 1 - self.mem.len();
+        self.mem.reserve(needed.max(GROWTH));
+        self.mem.resize(self.pointer + 1, 0);
+    }
+    /// Increments the selected cell.
+    pub fn increment(&mut self) {
+        let cell = self.cell_mut();
+        *cell = cell.wrapping_add(1);
+    }
+    /// Decrements the selected cell.
+    pub fn decrement(&mut self) {
+        let cell = self.cell_mut();
+        *cell = cell.wrapping_sub(1);
     }
-    #[allow(unused_must_use)]
-    pub fn run(&mut self, instruction: &Instruction) {
+    /// Moves to the next cell, growing the memory if needed.
+    pub fn forward(&mut self) {
+        self.pointer += 1;
+        self.cell_mut();
+    }
+    /// Moves to the previous cell, if there is one.
+    pub fn backward(&mut self) {
+        if self.pointer != 0 {
+            self.pointer -= 1;
+        }
+    }
+    /// Moves back to the first cell, keeping the memory.
+    pub fn reset_pointer(&mut self) {
+        self.pointer = 0;
+    }
+    /// Returns to a new state, with every cell zero, while keeping the room
+    /// the memory has grown to.
+    pub fn clear(&mut self) {
+        self.select_tape(0);
+        for bank in self.banks.iter_mut().skip(1) {
+            *bank = (vec![0], 0);
+        }
+        self.mem.clear();
+        self.mem.push(0);
+        self.pointer = 0;
+        self.outted = false;
+        self.queued.clear();
+        self.written = 0;
+    }
+    /// Writes the selected cell to `io`.
+    pub fn output(&mut self, io: &mut impl Io) -> Result<(), IoError> {
+        self.outted = true;
+        io.write(self.cell())?;
+        self.written += 1;
+        Ok(())
+    }
+    /// Reads a queued byte, or a byte from `io`, into the selected cell,
+    /// returning the byte read.
+    ///
+    /// Leaves the cell unchanged if there is no more input.
+    pub fn input(&mut self, io: &mut impl Io) -> Result<Option<u8>, IoError> {
+        if let Some(b) = self.queued.pop_front() {
+            *self.cell_mut() = b;
+            return Ok(Some(b));
+        }
+        let read = io.read()?;
+        if let Some(b) = read {
+            *self.cell_mut() = b;
+            self.outted = true;
+        }
+        Ok(read)
+    }
+    /// Runs a single instruction, reading from stdin and writing to stdout.
+    #[cfg(feature = "std")]
+    pub fn run(&mut self, instruction: &Instruction) -> Result<(), RuntimeError> {
         match instruction {
-            Instruction::Increment => {
-                self.mem[self.pointer] = self.mem[self.pointer].overflowing_add(1).0;
-            }
-            Instruction::Decrement => {
-                self.mem[self.pointer] = self.mem[self.pointer].overflowing_sub(1).0;
-            }
-            Instruction::Forward => {
-                if self.pointer + 1 == self.mem.len() {
-                    self.mem.push(0)
+            Instruction::Increment => self.increment(),
+            Instruction::Decrement => self.decrement(),
+            Instruction::Forward => self.forward(),
+            Instruction::Backward => self.backward(),
+            Instruction::Loop(inners) => {
+                while self.cell() != 0 {
+                    for inner in inners {
+                        self.run(inner)?;
+                    }
                 }
-                self.pointer += 1;
             }
-            Instruction::Backward => {
-                if self.pointer != 0 {
-                    self.pointer -= 1;
-                }
-            }
-            Instruction::Loop(inners) => loop {
-                for inner in inners {
-                    self.run(inner)
-                }
-                if self.mem[self.pointer] == 0 {
-                    break;
-                }
-            },
             Instruction::LoopEnd => {}
-            Instruction::Out => {
-                self.outted = true;
-                print!("{}", to_char_8859(self.mem[self.pointer]));
-                stdout().flush();
-            }
+            Instruction::Out => self.output(&mut StdIo::new(Iso8859_1))?,
             Instruction::In => {
-                if let Some(Ok(c)) = stdin().bytes().next() {
-                    let b = from_char_8859(c as char);
-                    self.mem[self.pointer] = b;
-                    self.outted = true;
-                }
+                self.input(&mut StdIo::new(Iso8859_1))?;
             }
         }
+        Ok(())
     }
 }
 
@@ -122,23 +371,36 @@ pub enum Instruction {
     In,
 }
 
-#[derive(Debug)]
-pub struct TryFromCharError;
-
-impl std::fmt::Display for TryFromCharError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Could not convert from a `char`.")
+impl fmt::Display for Instruction {
+    /// Writes the instruction as Brainfuck code, along with everything
+    /// inside it if it is a loop.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Increment => write!(f, "+"),
+            Instruction::Decrement => write!(f, "-"),
+            Instruction::Forward => write!(f, ">"),
+            Instruction::Backward => write!(f, "<"),
+            Instruction::Loop(inners) => {
+                write!(f, "[")?;
+                for inner in inners {
+                    write!(f, "{inner}")?;
+                }
+                write!(f, "]")
+            }
+            Instruction::LoopEnd => write!(f, "]"),
+            Instruction::Out => write!(f, "."),
+            Instruction::In => write!(f, ","),
+        }
     }
 }
 
-impl std::error::Error for TryFromCharError {}
-
 impl TryFrom<char> for Instruction {
-    type Error = TryFromCharError;
+    type Error = ParseError;
     /// Parses a character into a Brainfuck instruction.
     ///
-    /// If the input is not an instruction,
-    fn try_from(c: char) -> Result<Self, TryFromCharError> {
+    /// If the input is not an instruction, returns
+    /// [`ParseError::InvalidCharacter`].
+    fn try_from(c: char) -> Result<Self, ParseError> {
         use Instruction::*;
         Ok(match c {
             '+' => Increment,
@@ -149,7 +411,7 @@ impl TryFrom<char> for Instruction {
             ']' => LoopEnd,
             '.' => Out,
             ',' => In,
-            _ => return Err(TryFromCharError),
+            _ => return Err(ParseError::InvalidCharacter(c)),
         })
     }
 }
@@ -0,0 +1,271 @@
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::RangeInclusive;
+
+use crate::program::{chars_with_spans, Op, Program, Span};
+use crate::Instruction;
+
+/// A kind of mistake found by [`Program::lint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lint {
+    /// A loop which starts on a cell that is always zero, so it can
+    /// never execute.
+    DeadLoop,
+    /// A `<` which runs while the pointer is always on the first cell.
+    PointerUnderflow,
+    /// Code following a loop which can never finish.
+    Unreachable,
+    /// A character next to commands which looks like a mistyped command.
+    Typo {
+        /// The character that was written.
+        found: char,
+        /// The command it was likely meant to be.
+        expected: char,
+    },
+}
+
+/// A mistake found by [`Program::lint`], along with where it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// What the mistake is.
+    pub lint: Lint,
+    /// Where the mistake is.
+    pub span: Span,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Lint::DeadLoop => write!(f, "this loop can never execute."),
+            Lint::PointerUnderflow => write!(f, "`<` on the first cell does nothing."),
+            Lint::Unreachable => write!(f, "this code is unreachable after an infinite loop."),
+            Lint::Typo { found, expected } => {
+                write!(f, "`{found}` is not a command, did you mean `{expected}`?")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.lint)
+    }
+}
+
+/// Finds the command a character was likely meant to be.
+fn look_alike(c: char) -> Option<char> {
+    Some(match c {
+        ';' | ':' => '.',
+        '(' | '{' => '[',
+        ')' | '}' => ']',
+        '_' | '–' | '—' | '−' => '-',
+        '‹' | '«' => '<',
+        '›' | '»' => '>',
+        _ => return None,
+    })
+}
+
+fn is_command(c: char) -> bool {
+    Instruction::try_from(c).is_ok()
+}
+
+/// Whether the loop between `open` and `close` always returns to the
+/// cell it started on.
+fn is_balanced(ops: &[Op], open: usize, close: usize) -> bool {
+    let mut offset = 0isize;
+    let mut i = open + 1;
+    while i < close {
+        match ops[i] {
+            Op::Forward => offset += 1,
+            Op::Backward => offset -= 1,
+            Op::PrevTape | Op::NextTape => return false,
+            Op::Open(end) => {
+                if !is_balanced(ops, i, end) {
+                    return false;
+                }
+                i = end;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    offset == 0
+}
+
+/// The index of the `]` ending the loop `start` is in, or the length of
+/// `ops` if it is in none.
+fn enclosing_close(ops: &[Op], start: usize) -> usize {
+    let mut i = start;
+    while i < ops.len() {
+        match ops[i] {
+            Op::Open(close) => i = close,
+            Op::Close(_) => return i,
+            _ => {}
+        }
+        i += 1;
+    }
+    ops.len()
+}
+
+/// What is known about the process at some point of the program.
+struct Knowledge {
+    /// The location of the pointer, if known.
+    pointer: Option<usize>,
+    /// The value of the current cell, if known.
+    cell: Option<u8>,
+    /// Every non-zero cell, while the whole memory is known.
+    memory: Option<BTreeMap<usize, u8>>,
+}
+
+impl Knowledge {
+    fn recall(&mut self) {
+        self.cell = match (&self.memory, self.pointer) {
+            (Some(memory), Some(pointer)) => Some(memory.get(&pointer).copied().unwrap_or(0)),
+            _ => None,
+        };
+    }
+
+    fn change(&mut self, f: fn(u8) -> u8) {
+        self.cell = self.cell.map(f);
+        if let (Some(memory), Some(pointer), Some(cell)) =
+            (&mut self.memory, self.pointer, self.cell)
+        {
+            memory.insert(pointer, cell);
+        }
+    }
+
+    fn forget(&mut self) {
+        self.cell = None;
+        self.memory = None;
+    }
+}
+
+impl Program {
+    /// Looks for common mistakes in the program.
+    ///
+    /// A loop at the very start of a program is not reported as dead,
+    /// as it is commonly used to hold comments.
+    pub fn lint(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = self.lint_typos();
+        diagnostics.extend(self.lint_flow());
+        diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+        diagnostics
+    }
+
+    fn lint_typos(&self) -> Vec<Diagnostic> {
+        let chars: Vec<(char, Span)> = chars_with_spans(self.source()).collect();
+        let shebang = self.source().starts_with("#!");
+        let tapes = self
+            .ops()
+            .iter()
+            .any(|op| matches!(op, Op::PrevTape | Op::NextTape));
+        let mut diagnostics = vec![];
+        for (i, &(found, span)) in chars.iter().enumerate() {
+            if shebang && span.line == 1 {
+                continue;
+            }
+            let Some(expected) = look_alike(found) else {
+                continue;
+            };
+            if tapes && matches!(found, '{' | '}') {
+                continue;
+            }
+            let before = i > 0 && is_command(chars[i - 1].0);
+            let after = chars.get(i + 1).is_some_and(|&(c, _)| is_command(c));
+            if before || after {
+                diagnostics.push(Diagnostic {
+                    lint: Lint::Typo { found, expected },
+                    span,
+                });
+            }
+        }
+        diagnostics
+    }
+
+    fn lint_flow(&self) -> Vec<Diagnostic> {
+        self.flow()
+            .into_iter()
+            // A leading loop is never run, so it usually holds a comment.
+            .filter(|(lint, ops)| !(*lint == Lint::DeadLoop && *ops.start() == 0))
+            .map(|(lint, ops)| Diagnostic {
+                lint,
+                span: self.spans()[*ops.start()].to(self.spans()[*ops.end()]),
+            })
+            .collect()
+    }
+
+    /// Follows what is known about the memory through the program, finding
+    /// mistakes along with the operations they cover.
+    pub(crate) fn flow(&self) -> Vec<(Lint, RangeInclusive<usize>)> {
+        let ops = self.ops();
+        let mut findings = vec![];
+        let mut known = Knowledge {
+            pointer: Some(0),
+            cell: Some(0),
+            memory: Some(BTreeMap::new()),
+        };
+        // The pointer at the start of each open loop, if the loop is balanced.
+        let mut loops: Vec<Option<usize>> = vec![];
+        let mut i = 0;
+        while i < ops.len() {
+            match ops[i] {
+                Op::Increment => known.change(|cell| cell.wrapping_add(1)),
+                Op::Decrement => known.change(|cell| cell.wrapping_sub(1)),
+                Op::Forward => {
+                    known.pointer = known.pointer.map(|pointer| pointer + 1);
+                    known.recall();
+                }
+                Op::Backward => {
+                    if known.pointer == Some(0) {
+                        findings.push((Lint::PointerUnderflow, i..=i));
+                    }
+                    known.pointer = known.pointer.map(|pointer| pointer.saturating_sub(1));
+                    known.recall();
+                }
+                Op::Open(close) => {
+                    if known.cell == Some(0) {
+                        findings.push((Lint::DeadLoop, i..=close));
+                        i = close + 1;
+                        continue;
+                    }
+                    let inert = ops[i + 1..close].iter().all(|op| *op == Op::Out);
+                    if known.cell.is_some() && inert {
+                        // The loop this one is in may never be entered, so the
+                        // code after that loop can still run.
+                        let end = enclosing_close(ops, close + 1);
+                        if close + 1 < end {
+                            findings.push((Lint::Unreachable, close + 1..=end - 1));
+                        }
+                        if end == ops.len() {
+                            break;
+                        }
+                        i = end;
+                        continue;
+                    }
+                    if is_balanced(ops, i, close) {
+                        loops.push(known.pointer);
+                    } else {
+                        loops.push(None);
+                        known.pointer = None;
+                    }
+                    known.forget();
+                }
+                Op::Close(_) => {
+                    known.pointer = loops.pop().flatten();
+                    known.memory = None;
+                    known.cell = Some(0);
+                }
+                Op::Out => {}
+                Op::In | Op::Random => known.forget(),
+                Op::PrevTape | Op::NextTape => {
+                    known.pointer = None;
+                    known.forget();
+                }
+            }
+            i += 1;
+        }
+        findings
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::io::{BufReader, Read, Write};
+
+use serde_json::{json, Value};
+
+use crate::framing::{read_message, write_message};
+use crate::{Error, Op, ParseError, Program, Span};
+
+/// Serves the Language Server Protocol, so editors can show mistakes in
+/// Brainfuck files, jump between matching brackets, and describe loops when
+/// hovering over them.
+///
+/// Messages are read from `input` and written to `output`, which are
+/// usually stdin and stdout.
+pub fn serve(input: impl Read, mut output: impl Write) -> Result<(), Error> {
+    let mut input = BufReader::new(input);
+    let mut documents = HashMap::new();
+    while let Some(message) = read_message(&mut input)? {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                },
+                "serverInfo": { "name": "headaches" },
+            }),
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                let text = match method {
+                    "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+                    _ => params["contentChanges"]
+                        .as_array()
+                        .and_then(|changes| changes.last())
+                        .and_then(|change| change["text"].as_str()),
+                };
+                let document = Document::new(text.unwrap_or_default());
+                publish(&mut output, uri, document.diagnostics())?;
+                documents.insert(uri.to_string(), document);
+                continue;
+            }
+            "textDocument/didClose" => {
+                documents.remove(uri);
+                publish(&mut output, uri, vec![])?;
+                continue;
+            }
+            "textDocument/hover" => documents
+                .get(uri)
+                .and_then(|document| document.hover(&params["position"]))
+                .unwrap_or_default(),
+            "textDocument/definition" => documents
+                .get(uri)
+                .and_then(|document| document.definition(uri, &params["position"]))
+                .unwrap_or_default(),
+            "shutdown" => Value::Null,
+            "exit" => break,
+            _ if message["id"].is_null() => continue,
+            _ => {
+                let error =
+                    json!({ "code": -32601, "message": format!("`{method}` is not supported.") });
+                send(&mut output, json!({ "id": message["id"], "error": error }))?;
+                continue;
+            }
+        };
+        if !message["id"].is_null() {
+            send(
+                &mut output,
+                json!({ "id": message["id"], "result": result }),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// An open Brainfuck file.
+struct Document {
+    text: String,
+    program: Result<Program, ParseError>,
+}
+
+impl Document {
+    fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            program: Program::parse(text),
+        }
+    }
+
+    /// The problems with the file, as LSP diagnostics.
+    fn diagnostics(&self) -> Vec<Value> {
+        match &self.program {
+            Err(e) => {
+                let (span, message) = match *e {
+                    ParseError::UnmatchedOpen(span) => (span, "`[` is never closed."),
+                    ParseError::UnmatchedClose(span) => (span, "`]` has no matching `[`."),
+                    ParseError::InvalidCharacter(_) => return vec![],
+                };
+                vec![json!({ "range": self.range(span), "severity": 1, "message": message })]
+            }
+            Ok(program) => program
+                .lint()
+                .into_iter()
+                .map(|diagnostic| {
+                    json!({
+                        "range": self.range(diagnostic.span),
+                        "severity": 2,
+                        "message": diagnostic.lint.to_string(),
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// A description of the loop with a bracket at an LSP position.
+    fn hover(&self, position: &Value) -> Option<Value> {
+        let program = self.program.as_ref().ok()?;
+        let (bracket, partner) = self.bracket_at(position)?;
+        let (open, close) = (bracket.min(partner), bracket.max(partner));
+        let kind = program.loop_kind(open)?;
+        let span = program.spans()[open].to(program.spans()[close]);
+        Some(json!({
+            "contents": { "kind": "plaintext", "value": kind.to_string() },
+            "range": self.range(span),
+        }))
+    }
+
+    /// The location of the bracket matching the one at an LSP position.
+    fn definition(&self, uri: &str, position: &Value) -> Option<Value> {
+        let program = self.program.as_ref().ok()?;
+        let (_, partner) = self.bracket_at(position)?;
+        Some(json!({ "uri": uri, "range": self.range(program.spans()[partner]) }))
+    }
+
+    /// The index of a bracket at, or just before, an LSP position, along with
+    /// the index of its partner.
+    fn bracket_at(&self, position: &Value) -> Option<(usize, usize)> {
+        let program = self.program.as_ref().ok()?;
+        let line = position["line"].as_u64()? as usize;
+        let character = position["character"].as_u64()? as usize;
+        let offset = self.offset(line, character);
+        let at = program
+            .spans()
+            .iter()
+            .position(|span| span.start <= offset && offset < span.end);
+        let before = program.spans().iter().position(|span| span.end == offset);
+        [at, before]
+            .into_iter()
+            .flatten()
+            .find_map(|index| match program.ops()[index] {
+                Op::Open(partner) | Op::Close(partner) => Some((index, partner)),
+                _ => None,
+            })
+    }
+
+    /// The byte offset of an LSP line and UTF-16 character.
+    fn offset(&self, line: usize, character: usize) -> usize {
+        let start: usize = self
+            .text
+            .split_inclusive('\n')
+            .take(line)
+            .map(str::len)
+            .sum();
+        let mut units = 0;
+        for (i, c) in self.text[start..].char_indices() {
+            if units >= character || c == '\n' {
+                return start + i;
+            }
+            units += c.len_utf16();
+        }
+        self.text.len()
+    }
+
+    /// The LSP line and UTF-16 character of a byte offset.
+    fn position(&self, offset: usize) -> Value {
+        let before = &self.text[..offset];
+        let line = before.matches('\n').count();
+        let start = before.rfind('\n').map_or(0, |i| i + 1);
+        let character: usize = before[start..].chars().map(char::len_utf16).sum();
+        json!({ "line": line, "character": character })
+    }
+
+    fn range(&self, span: Span) -> Value {
+        json!({ "start": self.position(span.start), "end": self.position(span.end) })
+    }
+}
+
+/// Replaces the diagnostics shown for a file.
+fn publish(output: &mut impl Write, uri: &str, diagnostics: Vec<Value>) -> Result<(), Error> {
+    send(
+        output,
+        json!({
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        }),
+    )
+}
+
+fn send(output: &mut impl Write, mut message: Value) -> Result<(), Error> {
+    message["jsonrpc"] = "2.0".into();
+    Ok(write_message(output, &message)?)
+}
@@ -1,52 +1,1068 @@
-use std::io::{stdin, stdout, Write};
-use std::{fs::read_to_string, path::PathBuf};
+#[cfg(feature = "image")]
+use std::cell::RefCell;
+use std::fs::{read_to_string, File};
+use std::io::{stdin, stdout, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "image")]
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
 
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 
-use headaches::{run, run_from_state, State};
+use headaches::advise::advise;
+use headaches::batch;
+use headaches::sparse::{self, SparseTape};
+use headaches::transpile;
+#[cfg(feature = "image")]
+use headaches::TapeImage;
+#[cfg(feature = "terminal")]
+use headaches::TerminalIo;
+use headaches::{
+    Breakpoint, Buffer, CancelToken, Chain, Condition, Config, Controls, Cp437, Debugger, Edit,
+    Encoding, Error, FilterControls, Flush, Interpreter, Io, IoError, Iso8859_1, JsonTracer,
+    OptLevel, Profile, Program, RawStdIo, Record, Replay, RuntimeError, State, StdIo, Stop,
+    Streams, Table, TapeModel, Utf8StdIo, Windows1252, CLASSIC_CELLS,
+};
 
 /// A brainfuck interpreter.
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Cli {
-    /// Filename of .b file. If not used, opens repl.
+    /// Filename of .b file, or `-` to read it from stdin. If not used, opens repl.
     #[clap(value_parser)]
     filename: Option<PathBuf>,
+    /// Run this code instead of a file.
+    #[clap(short = 'e', long = "eval", value_name = "CODE", global = true)]
+    eval: Option<String>,
     /// Reveal all memory after running (prints each time on repl).
-    #[clap(short, long)]
+    #[clap(short, long, global = true)]
     reveal: bool,
+    /// Report which instructions were never executed after running a file.
+    #[clap(long, global = true)]
+    coverage: bool,
+    /// Optimize a file before running it, so it runs faster: 1 combines
+    /// instructions and replaces common loops, and 2, the default, also
+    /// removes dead code and runs the part which reads no input ahead of time.
+    #[clap(
+        short = 'O',
+        long,
+        value_name = "LEVEL",
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "2",
+        value_parser = clap::value_parser!(u8).range(0..=2),
+        conflicts_with_all = &["coverage", "trace", "delay", "show-steps"],
+        global = true
+    )]
+    optimize: Option<u8>,
+    /// Remove code which can never execute before running a file, reporting
+    /// what was removed.
+    #[clap(long, global = true)]
+    dead_code: bool,
+    /// Print the first N cells, or every cell used, and the pointer after
+    /// running a file.
+    #[clap(
+        long,
+        value_name = "N",
+        min_values = 0,
+        require_equals = true,
+        global = true
+    )]
+    dump_memory: Option<Option<usize>>,
+    /// Print how many steps, cells, and bytes of output running a file took,
+    /// and how long.
+    #[clap(long, global = true)]
+    stats: bool,
+    /// Follow the tape, overflow, and end of input semantics of a well
+    /// known implementation when running a file.
+    #[clap(long, value_enum, global = true)]
+    profile: Option<Semantics>,
+    /// How the cells of memory are stored when running a file. A sparse tape
+    /// only holds the pages of cells written to, for programs which use
+    /// cells far apart.
+    #[clap(
+        long,
+        value_enum,
+        conflicts_with_all = &["optimize", "coverage", "trace", "delay", "show-steps", "dump-memory"],
+        global = true
+    )]
+    tape: Option<TapeKind>,
+    /// How many tapes a file has. With more than one, `{` and `}` select the
+    /// previous and next tape, each with its own pointer.
+    #[clap(
+        long,
+        value_name = "N",
+        default_value = "1",
+        value_parser = clap::value_parser!(u16).range(1..),
+        global = true
+    )]
+    tapes: u16,
+    /// Make `?` write a pseudo-random byte to the cell, generated from this
+    /// seed, so the same seed always gives the same bytes.
+    #[clap(long, value_name = "SEED", global = true)]
+    seed: Option<u64>,
+    /// Exit with the value of a cell once a file halts, instead of 0.
+    #[clap(long, value_enum, value_name = "CELL", global = true)]
+    exit_code: Option<ExitCell>,
+    /// Write every event of running a file to a log as JSON Lines.
+    #[clap(long, value_name = "LOG", value_parser, global = true)]
+    trace: Option<PathBuf>,
+    /// Save the memory over time as a PNG when running a file, with a row of
+    /// pixels for every `--image-every` steps and one for the end.
+    #[cfg(feature = "image")]
+    #[clap(
+        long,
+        value_name = "FILE",
+        value_parser,
+        conflicts_with_all = &["optimize", "tape"],
+        global = true
+    )]
+    trace_image: Option<PathBuf>,
+    /// How many steps pass between the rows of `--trace-image`.
+    #[cfg(feature = "image")]
+    #[clap(long, value_name = "STEPS", default_value = "1000", global = true)]
+    image_every: u64,
+    /// Wait this many milliseconds after each instruction when running a file.
+    #[clap(long, value_name = "MS", global = true)]
+    delay: Option<u64>,
+    /// Print each instruction and the cell it leaves behind when running a file.
+    #[clap(long, global = true)]
+    show_steps: bool,
+    /// Read and write raw bytes instead of translating them to characters.
+    #[clap(long, global = true)]
+    raw: bool,
+    /// Decode output as UTF-8 instead of translating each byte to a character.
+    #[clap(long, conflicts_with = "raw", global = true)]
+    utf8: bool,
+    /// The character set bytes are translated through.
+    #[clap(long, value_enum, default_value = "iso-8859-1", global = true)]
+    encoding: Charset,
+    /// A file holding a custom character table, used instead of `--encoding`.
+    ///
+    /// JSON files hold an array of 256 characters or an object mapping bytes
+    /// to characters. Other files hold lines of a hexadecimal byte followed by
+    /// its character, such as `80 €`.
+    #[clap(long, value_parser, global = true)]
+    charset: Option<PathBuf>,
+    /// Which control characters to write as they are, instead of as spaces.
+    #[clap(long, value_enum, default_value = "keep", global = true)]
+    controls: ControlChars,
+    /// When output written to stdout is flushed, so it appears.
+    #[clap(long, value_enum, default_value = "byte", global = true)]
+    flush: FlushAfter,
+    /// Read each key as soon as it is pressed, instead of waiting for Enter.
+    #[cfg(feature = "terminal")]
+    #[clap(long, conflicts_with_all = &["raw", "utf8", "input", "output"], global = true)]
+    keys: bool,
+    /// Write every byte of input read to a log file, to be given again with `--replay`.
+    #[clap(long, value_name = "LOG", value_parser, global = true)]
+    record: Option<PathBuf>,
+    /// Read input from a log file written by `--record` before reading from stdin.
+    #[clap(
+        long,
+        value_name = "LOG",
+        value_parser,
+        conflicts_with = "record",
+        global = true
+    )]
+    replay: Option<PathBuf>,
+    /// Read the input of `,` from a file instead of stdin, as raw bytes with `--raw`.
+    #[clap(long, value_name = "FILE", value_parser, global = true)]
+    input: Option<PathBuf>,
+    /// Write the output of `.` to a file instead of stdout, as raw bytes with
+    /// `--raw` or `--utf8`, or the code written by `optimize`.
+    #[clap(short, long, value_name = "FILE", value_parser, global = true)]
+    output: Option<PathBuf>,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+/// The modes other than running a file or opening the repl.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run a file, the same as giving it without a command.
+    Run {
+        /// Filename of .b file, or `-` to read it from stdin.
+        #[clap(value_parser)]
+        filename: Option<PathBuf>,
+    },
+    /// Write a file as optimized Brainfuck code, which other interpreters
+    /// can run, as much as `--optimize` selects or fully.
+    Optimize {
+        /// Filename of .b file, or `-` to read it from stdin.
+        #[clap(value_parser)]
+        filename: Option<PathBuf>,
+        /// Also write a map from each piece of the optimized code to where it
+        /// came from in the file.
+        #[clap(long, value_name = "FILE", value_parser)]
+        source_map: Option<PathBuf>,
+    },
+    /// Write a file as C code, which a C compiler can build, optimized as
+    /// much as `--optimize` selects or fully.
+    Transpile {
+        /// Filename of .b file, or `-` to read it from stdin.
+        #[clap(value_parser)]
+        filename: Option<PathBuf>,
+        /// Also write a map from each line of the C code to where it came
+        /// from in the file.
+        #[clap(long, value_name = "FILE", value_parser)]
+        source_map: Option<PathBuf>,
+    },
+    /// Run a file, then suggest how it could be made faster or smaller from
+    /// what it did.
+    Advise {
+        /// Filename of .b file, or `-` to read it from stdin.
+        #[clap(value_parser)]
+        filename: Option<PathBuf>,
+    },
+    /// Run several files on the same input and limits, comparing how many
+    /// steps each takes, how long at each optimization level, and whether
+    /// they write the same output.
+    Bench {
+        /// Filenames of .b files.
+        #[clap(value_parser, required = true)]
+        filenames: Vec<PathBuf>,
+        /// The number of steps after which a file is stopped.
+        #[clap(long, value_name = "STEPS", default_value = "1000000000")]
+        max_steps: u64,
+    },
+    /// Compile a file written in the assembly language of `headaches::asm`
+    /// into Brainfuck code.
+    Asm {
+        /// Filename of the assembly file.
+        #[clap(value_parser)]
+        filename: PathBuf,
+    },
+    /// Serve the Language Server Protocol on stdin and stdout, for checking files in an editor.
+    Lsp,
+    /// Serve the Debug Adapter Protocol on stdin and stdout, for debugging from an editor.
+    Dap,
+    /// Step through a file, setting breakpoints and inspecting memory from a prompt.
+    Debug {
+        /// Filename of .b file.
+        #[clap(value_parser)]
+        filename: PathBuf,
+    },
+    /// Step through a file in the terminal, showing its tape, source, and output.
+    #[cfg(feature = "tui")]
+    Watch {
+        /// Filename of .b file.
+        #[clap(value_parser)]
+        filename: PathBuf,
+    },
+}
+
+/// The cells whose value can be exited with.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ExitCell {
+    /// The cell the pointer is on.
+    Current,
+    /// The first cell.
+    First,
+}
+
+/// The semantics profiles which can be selected from the command line.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Semantics {
+    /// 30,000 wrapping cells, leaving the cell unchanged at the end of input.
+    Classic,
+    /// As many cells as needed, failing on overflow, and 0 at the end of input.
+    Dbfi,
+    /// As many wrapping cells as needed, leaving the cell unchanged at the
+    /// end of input.
+    Modern,
+}
+
+/// The tape models which can be selected from the command line.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum TapeKind {
+    /// Every cell up to the furthest right the pointer has been.
+    Dense,
+    /// Only the pages of cells written to.
+    Sparse,
+}
+
+/// The control characters which can be kept from the command line.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ControlChars {
+    /// Write every control character.
+    Keep,
+    /// Write only tab, line feed, and carriage return.
+    Layout,
+    /// Write no control characters.
+    Hide,
+}
+
+impl From<ControlChars> for Controls {
+    fn from(controls: ControlChars) -> Self {
+        match controls {
+            ControlChars::Keep => Controls::Keep,
+            ControlChars::Layout => Controls::Layout,
+            ControlChars::Hide => Controls::Hide,
+        }
+    }
+}
+
+/// The flush policies which can be selected from the command line.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum FlushAfter {
+    /// After every byte, for interactive programs.
+    Byte,
+    /// After every line.
+    Line,
+    /// Once the program halts, for programs which write a lot of output.
+    Halt,
+}
+
+impl From<FlushAfter> for Flush {
+    fn from(flush: FlushAfter) -> Self {
+        match flush {
+            FlushAfter::Byte => Flush::Byte,
+            FlushAfter::Line => Flush::Line,
+            FlushAfter::Halt => Flush::Halt,
+        }
+    }
+}
+
+/// The character sets which can be selected from the command line.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Charset {
+    /// ISO-8859-1 (Latin-1).
+    #[clap(name = "iso-8859-1")]
+    Iso8859_1,
+    /// Code page 437, as used by DOS.
+    Cp437,
+    /// Windows-1252, as used by older Windows programs.
+    #[clap(name = "windows-1252")]
+    Windows1252,
+}
+
+impl Cli {
+    /// The configuration selected by `--profile` and `--optimize`.
+    fn config(&self) -> Config {
+        let config = match self.profile {
+            Some(Semantics::Classic) => Profile::Classic.config(),
+            Some(Semantics::Dbfi) => Profile::Dbfi.config(),
+            Some(Semantics::Modern) | None => Profile::Modern.config(),
+        };
+        Config {
+            opt_level: self.opt_level(),
+            tapes: self.tapes.into(),
+            random: self.seed,
+            tape: match self.tape {
+                Some(TapeKind::Sparse) => TapeModel::Sparse,
+                Some(TapeKind::Dense) | None => TapeModel::Dense,
+            },
+            ..config
+        }
+    }
+
+    /// The optimization level selected by `--optimize`.
+    fn opt_level(&self) -> OptLevel {
+        match self.optimize {
+            None | Some(0) => OptLevel::None,
+            Some(1) => OptLevel::Basic,
+            Some(_) => OptLevel::Full,
+        }
+    }
+
+    /// Connects to stdin and stdout as selected by the flags.
+    fn io(&self) -> Result<Box<dyn Io>, Error> {
+        let io = self.std_streams()?;
+        Ok(if let Some(log) = &self.record {
+            Box::new(Record::new(io, File::create(log)?))
+        } else if let Some(log) = &self.replay {
+            Box::new(Replay::new(&read_to_string(log)?, io)?)
+        } else {
+            io
+        })
+    }
+
+    /// Connects to stdin and stdout as selected by the flags, without
+    /// recording or replaying input.
+    fn std_streams(&self) -> Result<Box<dyn Io>, Error> {
+        if self.input.is_some() || self.output.is_some() {
+            return self.file_streams();
+        }
+        Ok(if self.raw {
+            Box::new(RawStdIo::with_flush(self.flush.into()))
+        } else if self.utf8 {
+            Box::new(Utf8StdIo::with_flush(self.flush.into()))
+        } else {
+            self.std_io(self.encoding()?)
+        })
+    }
+
+    /// Connects to the files given by `--input` and `--output`, and to stdin
+    /// or stdout for whichever is not given.
+    fn file_streams(&self) -> Result<Box<dyn Io>, Error> {
+        let input: Box<dyn Read> = match &self.input {
+            Some(path) => Box::new(BufReader::new(File::open(path)?)),
+            None => Box::new(stdin()),
+        };
+        let output: Box<dyn Write> = match &self.output {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(stdout()),
+        };
+        Ok(if self.raw || self.utf8 {
+            Box::new(Streams::raw(input, output))
+        } else {
+            let encoding = FilterControls::new(self.encoding()?, self.controls.into());
+            Box::new(Streams::new(input, output, encoding))
+        })
+    }
+
+    /// The character set selected by the flags.
+    fn encoding(&self) -> Result<Box<dyn Encoding>, Error> {
+        Ok(if let Some(charset) = &self.charset {
+            let contents = read_to_string(charset)?;
+            match charset.extension() {
+                Some(ext) if ext == "json" => Box::new(Table::from_json(&contents)?),
+                _ => Box::new(Table::from_text(&contents)?),
+            }
+        } else {
+            match self.encoding {
+                Charset::Iso8859_1 => Box::new(Iso8859_1),
+                Charset::Cp437 => Box::new(Cp437),
+                Charset::Windows1252 => Box::new(Windows1252),
+            }
+        })
+    }
+
+    /// Connects to stdin and stdout through `encoding`, filtering control
+    /// characters as selected by the flags.
+    fn std_io<E: Encoding + 'static>(&self, encoding: E) -> Box<dyn Io> {
+        let encoding = FilterControls::new(encoding, self.controls.into());
+        #[cfg(feature = "terminal")]
+        if self.keys {
+            return Box::new(TerminalIo::new(encoding));
+        }
+        Box::new(StdIo::new(encoding).with_flush(self.flush.into()))
+    }
 }
 
-#[allow(unused_must_use)]
 fn main() {
     let cli = Cli::parse();
 
-    match cli.filename {
-        Some(fp) => {
-            if let Ok(contents) = read_to_string(fp) {
-                let state = run(&String::from(contents));
-                if state.outted == true {
-                    println!()
+    let result = match (&cli.command, &cli.filename) {
+        (Some(Command::Dap), _) => cli
+            .encoding()
+            .and_then(|encoding| headaches::dap::serve(stdin(), stdout(), encoding)),
+        (Some(Command::Lsp), _) => headaches::lsp::serve(stdin(), stdout()),
+        (Some(Command::Debug { filename }), _) => debug_file(filename, &cli),
+        #[cfg(feature = "tui")]
+        (Some(Command::Watch { filename }), _) => watch_file(filename, &cli),
+        (Some(Command::Run { filename }), _) => run_file(filename.as_deref(), &cli),
+        (
+            Some(Command::Optimize {
+                filename,
+                source_map,
+            }),
+            _,
+        ) => optimize_file(filename.as_deref(), source_map.as_deref(), &cli),
+        (
+            Some(Command::Transpile {
+                filename,
+                source_map,
+            }),
+            _,
+        ) => transpile_file(filename.as_deref(), source_map.as_deref(), &cli),
+        (Some(Command::Advise { filename }), _) => advise_file(filename.as_deref(), &cli),
+        (Some(Command::Asm { filename }), _) => assemble_file(filename, &cli),
+        (
+            Some(Command::Bench {
+                filenames,
+                max_steps,
+            }),
+            _,
+        ) => bench_files(filenames, *max_steps, &cli),
+        (None, Some(filename)) => run_file(Some(filename), &cli),
+        (None, None) if cli.eval.is_some() => run_file(None, &cli),
+        (None, None) => {
+            repl(&cli);
+            Ok(())
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("{}", Chain(&e));
+        std::process::exit(1);
+    }
+}
+
+/// Compiles an assembly file into Brainfuck code.
+fn assemble_file(fp: &Path, cli: &Cli) -> Result<(), Error> {
+    let instructions = headaches::asm::compile(&read_to_string(fp)?)?;
+    let code: String = instructions.iter().map(ToString::to_string).collect();
+    match &cli.output {
+        Some(path) => std::fs::write(path, code + "\n")?,
+        None => println!("{code}"),
+    }
+    Ok(())
+}
+
+/// Writes a Brainfuck file as optimized Brainfuck code.
+fn optimize_file(fp: Option<&Path>, map: Option<&Path>, cli: &Cli) -> Result<(), Error> {
+    let program = load(fp, cli)?;
+    let config = Config {
+        opt_level: cli.optimize.map_or(OptLevel::Full, |_| cli.opt_level()),
+        ..Config::default()
+    };
+    let (code, source_map) = program.optimize_with(&config).to_brainfuck_with_map();
+    if let Some(path) = map {
+        std::fs::write(path, source_map.to_string())?;
+    }
+    match &cli.output {
+        Some(path) => std::fs::write(path, code + "\n")?,
+        None => println!("{code}"),
+    }
+    Ok(())
+}
+
+/// Writes a Brainfuck file as C code.
+fn transpile_file(fp: Option<&Path>, map: Option<&Path>, cli: &Cli) -> Result<(), Error> {
+    let program = load(fp, cli)?;
+    let config = Config {
+        opt_level: cli.optimize.map_or(OptLevel::Full, |_| cli.opt_level()),
+        ..cli.config()
+    };
+    let (code, source_map) = transpile::to_c(&program.optimize_with(&config), &config);
+    if let Some(path) = map {
+        std::fs::write(path, source_map.to_string())?;
+    }
+    match &cli.output {
+        Some(path) => std::fs::write(path, code)?,
+        None => print!("{code}"),
+    }
+    Ok(())
+}
+
+/// Runs Brainfuck files on the same input, printing a table comparing them.
+fn bench_files(fps: &[PathBuf], max_steps: u64, cli: &Cli) -> Result<(), Error> {
+    let input = match &cli.input {
+        Some(path) => std::fs::read(path)?,
+        None => vec![],
+    };
+    let config = Config {
+        max_steps: Some(max_steps),
+        ..cli.config()
+    };
+    let width = fps
+        .iter()
+        .map(|fp| fp.display().to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max("file".len());
+    println!(
+        "{:width$}  {:>8}  {:>12}  {:>10}  {:>10}  {:>10}  output",
+        "file", "ops", "steps", "-O=0", "-O=1", "-O=2"
+    );
+    let mut first: Option<Vec<u8>> = None;
+    for fp in fps {
+        let program = Program::parse_from_reader_with(File::open(fp)?, &config)?;
+        let bench = batch::bench(&program, &input, &config);
+        let outcome = &bench.outcome;
+        let mut times = [format!("{:.2?}", outcome.elapsed), "-".into(), "-".into()];
+        for (i, timing) in bench.timings.iter().enumerate() {
+            times[i + 1] = match timing.same_output {
+                true => format!("{:.2?}", timing.elapsed),
+                false => "differs".into(),
+            };
+        }
+        let output = match (&outcome.result, &first) {
+            (Err(e), _) => Chain(e).to_string(),
+            (Ok(()), None) => format!("{} bytes", outcome.output.len()),
+            (Ok(()), Some(first)) if *first == outcome.output => "same".into(),
+            (Ok(()), Some(_)) => "differs".into(),
+        };
+        if outcome.result.is_ok() && first.is_none() {
+            first = Some(outcome.output.clone());
+        }
+        println!(
+            "{:width$}  {:>8}  {:>12}  {:>10}  {:>10}  {:>10}  {output}",
+            fp.display(),
+            program.len(),
+            outcome.steps,
+            times[0],
+            times[1],
+            times[2],
+        );
+    }
+    Ok(())
+}
+
+/// Runs a Brainfuck file, then prints advice on it.
+fn advise_file(fp: Option<&Path>, cli: &Cli) -> Result<(), Error> {
+    let program = load(fp, cli)?;
+    let (advice, result) = advise(&program, cli.io()?, &cli.config());
+    println!();
+    if advice.is_empty() {
+        eprintln!("no advice.");
+    }
+    for advice in advice {
+        eprintln!("{advice}");
+    }
+    Ok(result?)
+}
+
+/// Runs a Brainfuck file to completion.
+fn run_file(fp: Option<&Path>, cli: &Cli) -> Result<(), Error> {
+    let mut program = load(fp, cli)?;
+    if cli.dead_code {
+        let (live, removed) = program.eliminate_dead_code();
+        for diagnostic in removed {
+            eprintln!("removed {diagnostic}");
+        }
+        program = live;
+    }
+    let start = Instant::now();
+    let config = cli.config();
+    if config.tape == TapeModel::Sparse {
+        return run_sparse(&program, cli, &config, start);
+    }
+    let mut state = State::with_capacity(CLASSIC_CELLS);
+    state.ensure_tapes(config.tapes);
+    if let Some(seed) = config.random {
+        state.rng = seed;
+    }
+    let optimized = config.opt_level != OptLevel::None && !config.checks_steps();
+    let (result, state, steps) = if optimized {
+        let result = program
+            .optimize_with(&config)
+            .run(&mut state, &mut cli.io()?);
+        (result, state, None)
+    } else {
+        let mut interpreter = Interpreter::builder()
+            .io(cli.io()?)
+            .state(state)
+            .config(&config)
+            .build(program);
+        if cli.coverage {
+            interpreter.track_coverage();
+        }
+        if let Some(log) = &cli.trace {
+            interpreter.add_hook(JsonTracer::new(BufWriter::new(File::create(log)?)));
+        }
+        #[cfg(feature = "image")]
+        let image = cli.trace_image.as_ref().map(|path| {
+            let image = Rc::new(RefCell::new(TapeImage::new(cli.image_every)));
+            interpreter.add_hook(Rc::clone(&image));
+            (path, image)
+        });
+        let result = if cli.delay.is_some() || cli.show_steps {
+            run_slowly(&mut interpreter, cli)
+        } else {
+            interpreter.run()
+        };
+        if interpreter.state().outted {
+            println!()
+        }
+        if let Some(coverage) = interpreter.coverage() {
+            eprintln!("{coverage}");
+        }
+        #[cfg(feature = "image")]
+        if let Some((path, image)) = image {
+            let mut image = image.borrow_mut();
+            image.record(interpreter.state());
+            image.save(path)?;
+        }
+        let steps = interpreter.steps();
+        (result, interpreter.into_state(), Some(steps))
+    };
+    if optimized && state.outted {
+        println!()
+    }
+    if let Some(cells) = cli.dump_memory {
+        let dump = match cells {
+            Some(cells) => state.dump(..cells, &cli.encoding()?),
+            None => state.dump(.., &cli.encoding()?),
+        };
+        eprint!("{dump}");
+        eprintln!("pointer: {}", state.pointer);
+    }
+    if cli.stats {
+        if let Some(steps) = steps {
+            eprintln!("steps: {steps}");
+        }
+        eprintln!("cells: {}", state.mem.len());
+        eprintln!("output: {} bytes", state.written);
+        eprintln!("time: {:?}", start.elapsed());
+    }
+    result?;
+    if let Some(cell) = cli.exit_code {
+        let code = match cell {
+            ExitCell::Current => state.cell(),
+            ExitCell::First => state.mem.first().copied().unwrap_or(0),
+        };
+        std::process::exit(code.into());
+    }
+    Ok(())
+}
+
+/// Runs a Brainfuck file to completion on a sparse tape.
+fn run_sparse(program: &Program, cli: &Cli, config: &Config, start: Instant) -> Result<(), Error> {
+    let mut tape = SparseTape::new();
+    tape.rng = config.random.unwrap_or_default();
+    let result = sparse::run(program, &mut tape, &mut cli.io()?, config);
+    if tape.written > 0 {
+        println!()
+    }
+    if cli.stats {
+        if let Ok(steps) = result {
+            eprintln!("steps: {steps}");
+        }
+        eprintln!("cells: {} in {} pages", tape.cells(), tape.pages());
+        eprintln!("output: {} bytes", tape.written);
+        eprintln!("time: {:?}", start.elapsed());
+    }
+    result?;
+    if let Some(cell) = cli.exit_code {
+        let code = match cell {
+            ExitCell::Current => tape.cell(),
+            ExitCell::First => tape.get(0),
+        };
+        std::process::exit(code.into());
+    }
+    Ok(())
+}
+
+/// Loads the program given by `--eval`, or read from a file or stdin.
+fn load(fp: Option<&Path>, cli: &Cli) -> Result<Program, Error> {
+    match (&cli.eval, fp) {
+        (Some(code), None) => Ok(Program::parse_with(code, &cli.config())?),
+        (None, Some(fp)) if fp == Path::new("-") => {
+            Program::parse_from_reader_with(stdin(), &cli.config())
+        }
+        (None, Some(fp)) => Program::parse_from_reader_with(File::open(fp)?, &cli.config()),
+        (Some(_), Some(_)) => Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "A file cannot be given along with `--eval`.",
+            )
+            .exit(),
+        (None, None) => Cli::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "A file or `--eval` should be given.",
+            )
+            .exit(),
+    }
+}
+
+/// Steps through a Brainfuck file in the terminal.
+#[cfg(feature = "tui")]
+fn watch_file(fp: &Path, cli: &Cli) -> Result<(), Error> {
+    let program = Program::parse(&read_to_string(fp)?)?;
+    headaches::watch(program, cli.encoding()?)
+}
+
+/// Runs a program one instruction at a time, waiting and printing between
+/// instructions as selected by the flags.
+fn run_slowly<I: Io>(interpreter: &mut Interpreter<I>, cli: &Cli) -> Result<(), RuntimeError> {
+    let delay = Duration::from_millis(cli.delay.unwrap_or(0));
+    while !interpreter.is_halted() {
+        let position = interpreter.position();
+        interpreter.step()?;
+        if cli.show_steps {
+            let span = interpreter.program().spans()[position];
+            let instruction = interpreter.program().ops()[position];
+            let state = interpreter.state();
+            eprintln!(
+                "{span} {instruction} cell {} = {}",
+                state.pointer,
+                state.cell()
+            );
+        }
+        sleep(delay);
+    }
+    Ok(interpreter.io_mut().flush()?)
+}
+
+/// The commands understood by `debug`.
+const DEBUG_HELP: &str = "\
+Commands:
+    step [n]                   Execute the next instruction, or the next n.
+    continue                   Execute until a breakpoint is reached.
+    break <line>[:<col>] [if <condition>]
+                               Stop before the instruction at a location, if
+                               the condition holds, such as `cell[3] > 100`.
+    delete <id>                Remove a breakpoint.
+    list                       Show the breakpoints.
+    print                      Show the memory.
+    set <cell|@ptr> <value>    Change a cell or the pointer.
+    quit                       Stop debugging.";
+
+/// Steps through a Brainfuck file, reading commands from stdin.
+fn debug_file(fp: &Path, cli: &Cli) -> Result<(), Error> {
+    let encoding = cli.encoding()?;
+    let program = Program::parse(&read_to_string(fp)?)?;
+    let mut debugger = Debugger::new(Interpreter::with_io(program, State::new(), cli.io()?));
+    show_position(&mut debugger);
+    loop {
+        print!("(debug) ");
+        stdout().flush()?;
+        let mut raw = String::new();
+        if stdin().read_line(&mut raw)? == 0 {
+            break;
+        }
+        let line = raw.trim();
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let result = match command {
+            "" => continue,
+            "s" | "step" => match args {
+                "" => Some(1),
+                n => n.parse().ok(),
+            }
+            .map(|n: u64| (0..n).try_for_each(|_| debugger.step())),
+            "c" | "continue" => Some(debugger.resume().map(|stop| {
+                if let Stop::Breakpoint(id) = stop {
+                    println!("Stopped at breakpoint {id}.");
+                }
+            })),
+            "b" | "break" => parse_breakpoint(debugger.interpreter().program(), args).map(|b| {
+                println!("Added breakpoint {}.", debugger.add_breakpoint(b));
+                Ok(())
+            }),
+            "d" | "delete" => args
+                .parse()
+                .ok()
+                .and_then(|id| debugger.remove_breakpoint(id))
+                .map(|_| Ok(())),
+            "l" | "list" => {
+                for (id, breakpoint) in debugger.breakpoints() {
+                    let span = debugger.interpreter().program().spans()[breakpoint.position];
+                    match &breakpoint.condition {
+                        Some(condition) => println!("{id}: {span} if {condition}"),
+                        None => println!("{id}: {span}"),
+                    }
                 }
+                Some(Ok(()))
             }
+            "p" | "print" => {
+                print!("{}", debugger.interpreter().state().dump(.., &encoding));
+                Some(Ok(()))
+            }
+            "set" => Edit::parse(line).map(|edit| {
+                edit.apply(debugger.interpreter_mut().state_mut());
+                Ok(())
+            }),
+            "q" | "quit" => break,
+            _ => None,
+        };
+        match result {
+            Some(Ok(())) => {}
+            Some(Err(e)) => eprintln!("{}", Chain(&e)),
+            None => eprintln!("{DEBUG_HELP}"),
+        }
+        if matches!(command, "s" | "step" | "c" | "continue") {
+            show_position(&mut debugger);
         }
-        None => {
-            let mut state = State::new();
-            loop {
-                print!(">>> ");
-                stdout().flush();
-                let mut raw = String::new();
-                stdin().read_line(&mut raw).expect("could not read input");
-                run_from_state(&raw, &mut state);
-                if state.outted == true {
-                    println!("");
-                    state.outted = false;
+    }
+    Ok(())
+}
+
+/// Parses the arguments of `break`, such as `3:5 if cell[3] > 100`.
+fn parse_breakpoint(program: &Program, args: &str) -> Option<Breakpoint> {
+    let (location, condition) = match args.split_once(" if ") {
+        Some((location, condition)) => (location, Some(Condition::parse(condition)?)),
+        None => (args, None),
+    };
+    let (line, column) = match location.trim().split_once(':') {
+        Some((line, column)) => (line.parse().ok()?, column.parse().ok()?),
+        None => (location.trim().parse().ok()?, 1),
+    };
+    Some(Breakpoint {
+        position: program.position_at(line, column)?,
+        condition,
+    })
+}
+
+/// Prints the next instruction and the selected cell of a debugged program.
+fn show_position<I: Io>(debugger: &mut Debugger<I>) {
+    let interpreter = debugger.interpreter_mut();
+    if interpreter.state().outted {
+        println!();
+        interpreter.state_mut().outted = false;
+    }
+    if interpreter.is_halted() {
+        println!("Halted after {} steps.", interpreter.steps());
+        return;
+    }
+    let position = interpreter.position();
+    let span = interpreter.program().spans()[position];
+    let op = interpreter.program().ops()[position];
+    let state = interpreter.state();
+    println!(
+        "{span}: {op} (pointer {}, cell {})",
+        state.pointer,
+        state.cell()
+    );
+}
+
+/// A line entered into the repl which changed the state.
+enum Entry {
+    /// Code which was run, along with the input it read and how many steps
+    /// it took, so it can be run again the same way.
+    Code {
+        program: Program,
+        input: Vec<u8>,
+        steps: u64,
+    },
+    /// A `:set` command.
+    Edit(Edit),
+}
+
+/// Remembers the bytes read through an `Io`.
+struct Captured<'a> {
+    io: &'a mut dyn Io,
+    read: Vec<u8>,
+}
+
+impl Io for Captured<'_> {
+    fn read(&mut self) -> Result<Option<u8>, IoError> {
+        let byte = self.io.read()?;
+        self.read.extend(byte);
+        Ok(byte)
+    }
+    fn write(&mut self, byte: u8) -> Result<(), IoError> {
+        self.io.write(byte)
+    }
+    fn flush(&mut self) -> Result<(), IoError> {
+        self.io.flush()
+    }
+}
+
+/// Reads and runs Brainfuck code line by line, keeping the state between lines.
+///
+/// Lines starting with `:` are commands instead, such as `:set 12 65`, or
+/// `:undo` to take back the last line by running every line before it again.
+fn repl(cli: &Cli) {
+    let config = cli.config();
+    // The state is seeded once, so each line carries on from where the
+    // one before left the generator instead of starting it over.
+    let lines = Config {
+        random: None,
+        ..config
+    };
+    let fresh = || {
+        let mut state = State::with_tapes(config.tapes);
+        state.rng = config.random.unwrap_or_default();
+        state
+    };
+    let mut state = fresh();
+    let mut history: Vec<Entry> = vec![];
+    let mut io = match cli.io() {
+        Ok(io) => io,
+        Err(e) => {
+            eprintln!("{}", Chain(&e));
+            std::process::exit(1);
+        }
+    };
+    // Ctrl-C stops the snippet being run, or leaves the repl at the prompt.
+    let running: Arc<Mutex<Option<CancelToken>>> = Arc::default();
+    let handler = Arc::clone(&running);
+    let trapped = ctrlc::set_handler(move || match handler.lock().unwrap().as_ref() {
+        Some(token) => token.cancel(),
+        None => std::process::exit(130),
+    });
+    if let Err(e) = trapped {
+        eprintln!("{}", Chain(&e));
+    }
+    loop {
+        print!(">>> ");
+        if let Err(e) = stdout().flush() {
+            eprintln!("{}", Chain(&e));
+        }
+        let mut raw = String::new();
+        match stdin().read_line(&mut raw) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("{}", Chain(&e));
+                break;
+            }
+        }
+        if raw.trim() == ":undo" {
+            if history.pop().is_none() {
+                eprintln!("Nothing to undo.");
+            }
+            state = fresh();
+            for entry in &history {
+                state = match entry {
+                    Entry::Code {
+                        program,
+                        input,
+                        steps,
+                    } => {
+                        let mut interpreter = Interpreter::builder()
+                            .io(Buffer::new(input.clone()))
+                            .state(state)
+                            .config(&lines)
+                            .max_steps(*steps)
+                            .build(program.clone());
+                        // A line which did not halt stops at the step it
+                        // stopped at the first time.
+                        match interpreter.run() {
+                            Ok(()) | Err(RuntimeError::StepLimit(..)) => {}
+                            Err(e) => eprintln!("{}", Chain(&e)),
+                        }
+                        interpreter.into_state()
+                    }
+                    Entry::Edit(edit) => {
+                        edit.apply(&mut state);
+                        state
+                    }
+                };
+            }
+            state.outted = false;
+        } else if let Some(command) = raw.trim().strip_prefix(':') {
+            match Edit::parse(command) {
+                Some(edit) => {
+                    edit.apply(&mut state);
+                    history.push(Entry::Edit(edit));
                 }
-                if cli.reveal {
-                    print!("{:?}\n", state);
-                    stdout().flush();
+                None => eprintln!(
+                    "Unknown command, try `:set <cell> <value>`, `:set @ptr <cell>`, or `:undo`."
+                ),
+            }
+        } else {
+            match Program::parse_with(&raw, &config) {
+                Ok(program) => {
+                    let captured = Captured {
+                        io: &mut io,
+                        read: vec![],
+                    };
+                    let mut interpreter = Interpreter::builder()
+                        .io(captured)
+                        .state(state)
+                        .config(&lines)
+                        .build(program.clone());
+                    *running.lock().unwrap() = Some(interpreter.cancel_token());
+                    let result = interpreter.run();
+                    *running.lock().unwrap() = None;
+                    if let Err(e) = result {
+                        eprintln!("{}", Chain(&e));
+                    }
+                    let steps = interpreter.steps();
+                    let (new, captured) = interpreter.into_parts();
+                    state = new;
+                    history.push(Entry::Code {
+                        program,
+                        input: captured.read,
+                        steps,
+                    });
                 }
+                Err(e) => eprintln!("{}", Chain(&e)),
             }
         }
+        if state.outted {
+            println!();
+            state.outted = false;
+        }
+        if cli.reveal {
+            println!("{:?}", state);
+        }
     }
 }
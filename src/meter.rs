@@ -0,0 +1,59 @@
+use std::time::{Duration, Instant};
+
+use crate::{Interpreter, Io, RuntimeError, State};
+
+/// How much a run used of what it was allowed, returned by
+/// [`Interpreter::run_metered`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeterReport {
+    /// The number of instructions executed.
+    pub steps: u64,
+    /// The most cells, one byte each, the memory of every tape held at once.
+    pub peak_memory: usize,
+    /// How long the program ran for.
+    pub elapsed: Duration,
+    /// Whether the run stopped because it reached the step or memory limit.
+    pub tripped: bool,
+}
+
+impl<I: Io> Interpreter<I> {
+    /// Executes the program until it halts, like [`run`](Self::run), also
+    /// reporting the resources it used.
+    ///
+    /// The steps and memory count everything since the interpreter was
+    /// created, while the time only counts this call.
+    pub fn run_metered(&mut self) -> (Result<(), RuntimeError>, MeterReport) {
+        let start = Instant::now();
+        let mut peak_memory = cells(self.state());
+        // The selected memory only changes length when it grows or another
+        // tape is selected, so the total is only counted again then.
+        let mut len = self.state().mem.len();
+        let result = loop {
+            if self.is_halted() {
+                break self.io_mut().flush().map_err(RuntimeError::from);
+            }
+            if let Err(e) = self.step() {
+                break Err(e);
+            }
+            if self.state().mem.len() != len {
+                len = self.state().mem.len();
+                peak_memory = peak_memory.max(cells(self.state()));
+            }
+        };
+        let report = MeterReport {
+            steps: self.steps(),
+            peak_memory,
+            elapsed: start.elapsed(),
+            tripped: matches!(
+                result,
+                Err(RuntimeError::StepLimit(..) | RuntimeError::MemoryLimit(_))
+            ),
+        };
+        (result, report)
+    }
+}
+
+/// The cells held by every tape of a state.
+fn cells(state: &State) -> usize {
+    state.mem.len() + state.banks.iter().map(|(mem, _)| mem.len()).sum::<usize>()
+}
@@ -0,0 +1,1098 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+use core::{fmt, iter};
+
+use crate::{
+    Buffer, Config, Diagnostic, Eof, Interpreter, Io, Lint, Op, OptLevel, Program, RuntimeError,
+    Span, State,
+};
+
+/// The most steps [`OptLevel::Full`] runs ahead of time with
+/// [`Program::fold_prefix`].
+pub const FOLD_STEPS: u64 = 1_000_000;
+
+/// What a loop does, as recognized by [`Program::loop_kind`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoopKind {
+    /// Sets the cell to zero, such as `[-]`.
+    Clear,
+    /// Adds the cell, multiplied by a factor, to the cells at each offset,
+    /// then sets it to zero, such as `[->+>++<<]`.
+    ///
+    /// Factors wrap, so 255 subtracts the cell.
+    MulAdd(Vec<(isize, u8)>),
+    /// Moves the pointer by a stride until it reaches a zero cell, such
+    /// as `[>>]`.
+    Scan(isize),
+    /// Any other loop.
+    Other,
+}
+
+impl LoopKind {
+    /// Whether the loop copies the cell to each of its targets, adding it
+    /// once to each.
+    pub fn is_copy(&self) -> bool {
+        matches!(self, LoopKind::MulAdd(targets) if targets.iter().all(|&(_, factor)| factor == 1))
+    }
+}
+
+impl fmt::Display for LoopKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoopKind::Clear => write!(f, "clear loop: sets the cell to 0"),
+            LoopKind::MulAdd(targets) => {
+                if self.is_copy() {
+                    write!(f, "copy loop: adds the cell to the cells at ")?;
+                } else {
+                    write!(f, "multiply loop: adds the cell to the cells at ")?;
+                }
+                for (i, &(offset, factor)) in targets.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match factor {
+                        1 => write!(f, "{offset:+}")?,
+                        255 => write!(f, "{offset:+} negated")?,
+                        _ => write!(f, "{offset:+} times {factor}")?,
+                    }
+                }
+                write!(f, ", then sets it to 0")
+            }
+            LoopKind::Scan(stride) if *stride > 0 => {
+                write!(f, "scan loop: moves right by {stride} until a zero cell")
+            }
+            LoopKind::Scan(stride) => {
+                write!(f, "scan loop: moves left by {} until a zero cell", -stride)
+            }
+            LoopKind::Other => write!(f, "loop"),
+        }
+    }
+}
+
+impl Program {
+    /// Recognizes what the loop starting at the [`Op::Open`] at `open` does.
+    ///
+    /// Returns `None` if there is no loop starting there.
+    pub fn loop_kind(&self, open: usize) -> Option<LoopKind> {
+        let Some(&Op::Open(close)) = self.ops().get(open) else {
+            return None;
+        };
+        let Some((offset, mut deltas)) = deltas(&self.ops()[open + 1..close]) else {
+            return Some(LoopKind::Other);
+        };
+
+        if offset != 0 {
+            return Some(if deltas.is_empty() {
+                LoopKind::Scan(offset)
+            } else {
+                LoopKind::Other
+            });
+        }
+        // Counting down runs the loop once per unit of the cell, while counting
+        // up runs it once per unit short of 256, which negates every factor.
+        let negate = match deltas.remove(&0) {
+            Some(255) => false,
+            Some(1) => true,
+            _ => return Some(LoopKind::Other),
+        };
+        if deltas.is_empty() {
+            return Some(LoopKind::Clear);
+        }
+        let targets: Vec<_> = deltas
+            .into_iter()
+            .map(|(offset, factor)| {
+                if negate {
+                    (offset, factor.wrapping_neg())
+                } else {
+                    (offset, factor)
+                }
+            })
+            .collect();
+        Some(LoopKind::MulAdd(targets))
+    }
+}
+
+impl Program {
+    /// Removes code which can never execute, returning the smaller program
+    /// along with a [`Lint::DeadLoop`] or [`Lint::Unreachable`] for each
+    /// part removed.
+    ///
+    /// Loops which start on a cell that is always zero are removed, such as
+    /// a loop at the start of the program or right after another loop, as
+    /// is everything after a loop which never finishes. The smaller program
+    /// keeps the same source, so its spans still point into it.
+    pub fn eliminate_dead_code(&self) -> (Program, Vec<Diagnostic>) {
+        let mut keep = vec![true; self.len()];
+        let mut removed = vec![];
+        for (lint, ops) in self.flow() {
+            if matches!(lint, Lint::DeadLoop | Lint::Unreachable) {
+                let span = self.spans()[*ops.start()].to(self.spans()[*ops.end()]);
+                keep[ops].fill(false);
+                removed.push(Diagnostic { lint, span });
+            }
+        }
+        (self.retain(&keep), removed)
+    }
+}
+
+/// How far `ops` move the pointer, and how much they add to the cells at
+/// each offset from where they start, if they only move and add.
+fn deltas(ops: &[Op]) -> Option<(isize, BTreeMap<isize, u8>)> {
+    let mut offset = 0isize;
+    let mut deltas = BTreeMap::new();
+    for op in ops {
+        match op {
+            Op::Increment => {
+                let delta: &mut u8 = deltas.entry(offset).or_default();
+                *delta = delta.wrapping_add(1);
+            }
+            Op::Decrement => {
+                let delta: &mut u8 = deltas.entry(offset).or_default();
+                *delta = delta.wrapping_sub(1);
+            }
+            Op::Forward => offset += 1,
+            Op::Backward => offset -= 1,
+            _ => return None,
+        }
+    }
+    deltas.retain(|_, delta| *delta != 0);
+    Some((offset, deltas))
+}
+
+/// Whether `targets` include the furthest left cell a loop moves to, so
+/// updating them finds any move left of the first cell.
+fn reaches(targets: &[(isize, u8)], lowest: isize) -> bool {
+    targets
+        .iter()
+        .map(|&(offset, _)| offset)
+        .fold(0, isize::min)
+        == lowest
+}
+
+/// An operation of an [`Optimized`] program.
+///
+/// Offsets are relative to the pointer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ir {
+    /// Adds to the selected cell, wrapping.
+    Add(u8),
+    /// Moves the pointer, stopping at the first cell.
+    Move(isize),
+    /// Sets the selected cell to zero.
+    Clear,
+    /// Adds the selected cell, multiplied by a factor, to the cells at each
+    /// offset, then sets it to zero, like [`LoopKind::MulAdd`].
+    MulAdd(Vec<(isize, u8)>),
+    /// Adds to the cells at each offset, wrapping, without moving the
+    /// pointer. Loops which add and move but end where they started have
+    /// their bodies fused into one of these.
+    Update(Vec<(isize, u8)>),
+    /// Moves the pointer by a stride until it reaches a zero cell.
+    Scan(isize),
+    /// Sets cells to zero, moving by a stride after each, until it reaches
+    /// a zero cell, such as `[[-]>]`.
+    Sweep(isize),
+    /// Sets `len` cells, starting at an offset, to zero.
+    Fill {
+        /// The offset of the first cell.
+        from: isize,
+        /// The number of cells.
+        len: usize,
+    },
+    /// Like [`Op::Open`], holding the index of the matching [`Ir::Close`].
+    Open(usize),
+    /// Like [`Op::Close`], holding the index of the matching [`Ir::Open`].
+    Close(usize),
+    /// Replaces the memory and moves the pointer, as the start of a program
+    /// folded by [`Program::fold_prefix`] left them.
+    Load {
+        /// Every cell of the memory.
+        cells: Vec<u8>,
+        /// Where the pointer was left.
+        pointer: usize,
+    },
+    /// Writes bytes, as the start of a program folded by
+    /// [`Program::fold_prefix`] wrote them.
+    Write(Vec<u8>),
+    /// Writes the selected cell.
+    Out,
+    /// Reads into the selected cell.
+    In,
+    /// Selects the previous tape, like [`Op::PrevTape`].
+    PrevTape,
+    /// Selects the next tape, like [`Op::NextTape`].
+    NextTape,
+    /// Writes a pseudo-random byte to the selected cell, like [`Op::Random`].
+    Random,
+}
+
+/// Where a piece of code written by [`Optimized::to_brainfuck_with_map`]
+/// or [`transpile::to_c`](crate::transpile::to_c) came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping {
+    /// The byte offsets of the piece in the written Brainfuck code, or the
+    /// lines, counting from 1, of the written C code.
+    pub generated: Range<usize>,
+    /// Where the operations it was written for are in the original source.
+    pub original: Span,
+}
+
+/// Links code written by [`Optimized::to_brainfuck_with_map`] or
+/// [`transpile::to_c`](crate::transpile::to_c) back to the source of the
+/// program it was optimized from.
+///
+/// Written with [`Display`](fmt::Display), each mapping is a line holding
+/// where it is in the written code, the byte offsets of the original
+/// source, and its line and column, such as `0..12 0..26 1:1`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap(pub(crate) Vec<Mapping>);
+
+impl SourceMap {
+    /// Every mapping, in the order of the written code.
+    pub fn mappings(&self) -> &[Mapping] {
+        &self.0
+    }
+
+    /// The span of the original source a byte offset of the written
+    /// Brainfuck code, or a line of the written C code, came from.
+    pub fn lookup(&self, offset: usize) -> Option<Span> {
+        let i = self.0.partition_point(|m| m.generated.end <= offset);
+        self.0
+            .get(i)
+            .filter(|m| m.generated.contains(&offset))
+            .map(|m| m.original)
+    }
+}
+
+impl fmt::Display for SourceMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for m in &self.0 {
+            writeln!(
+                f,
+                "{}..{} {}..{} {}",
+                m.generated.start, m.generated.end, m.original.start, m.original.end, m.original
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A program lowered by [`Program::optimize`] into operations which do more
+/// at once.
+#[derive(Debug, Clone)]
+pub struct Optimized {
+    ir: Vec<Ir>,
+    spans: Vec<Span>,
+    eof: Eof,
+}
+
+impl Program {
+    /// Lowers the program into fewer, larger operations which run faster.
+    ///
+    /// Runs of `+` and `-`, and of `>` and `<`, are combined, and loops which
+    /// clear, copy, or scan are replaced by single operations. Other loops
+    /// which only add and move, ending where they started, update cells by
+    /// their offsets instead of moving to each.
+    ///
+    /// The optimized program assumes it never moves left of the first cell
+    /// while clearing or copying. If it would, it fails with
+    /// [`RuntimeError::PointerUnderflow`] instead of staying on the first
+    /// cell.
+    pub fn optimize(&self) -> Optimized {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("optimize", ops = self.len()).entered();
+        let mut optimized = Optimized {
+            ir: vec![],
+            spans: vec![],
+            eof: Eof::Unchanged,
+        };
+        let ops = self.ops();
+        let spans = self.spans();
+        let mut i = 0;
+        while i < ops.len() {
+            let span = spans[i];
+            match ops[i] {
+                Op::Increment | Op::Decrement => {
+                    let mut value = 0u8;
+                    while let Some(op @ (Op::Increment | Op::Decrement)) = ops.get(i) {
+                        value = match op {
+                            Op::Increment => value.wrapping_add(1),
+                            _ => value.wrapping_sub(1),
+                        };
+                        i += 1;
+                    }
+                    if value != 0 {
+                        optimized.push(Ir::Add(value), span.to(spans[i - 1]));
+                    }
+                    continue;
+                }
+                Op::Forward | Op::Backward => {
+                    let (by, len) = moves(&ops[i..]);
+                    if by != 0 {
+                        optimized.push(Ir::Move(by), span.to(spans[i + len - 1]));
+                    }
+                    i += len;
+                    continue;
+                }
+                Op::Open(close) => {
+                    let span = span.to(spans[close]);
+                    let lowest = lowest(&ops[i + 1..close]);
+                    match self.lower_loop(i) {
+                        Some(ir) => {
+                            optimized.push(ir, span);
+                            i = close + 1;
+                            continue;
+                        }
+                        None => optimized.push(Ir::Open(0), spans[i]),
+                    }
+                    if let Some((0, deltas)) = deltas(&ops[i + 1..close]) {
+                        let targets: Vec<_> = deltas.into_iter().collect();
+                        if i + 1 < close && reaches(&targets, lowest) {
+                            let body = spans[i + 1].to(spans[close - 1]);
+                            optimized.push(Ir::Update(targets), body);
+                            optimized.push(Ir::Close(0), spans[close]);
+                            i = close + 1;
+                            continue;
+                        }
+                    }
+                }
+                Op::Close(_) => optimized.push(Ir::Close(0), span),
+                Op::Out => optimized.push(Ir::Out, span),
+                Op::In => optimized.push(Ir::In, span),
+                Op::PrevTape => optimized.push(Ir::PrevTape, span),
+                Op::NextTape => optimized.push(Ir::NextTape, span),
+                Op::Random => optimized.push(Ir::Random, span),
+            }
+            i += 1;
+        }
+        optimized.fill_ranges();
+        optimized.link();
+        optimized
+    }
+
+    /// Optimizes the program as much as `config` chooses.
+    pub fn optimize_with(&self, config: &Config) -> Optimized {
+        let mut optimized = match config.opt_level {
+            OptLevel::None => self.lower(),
+            OptLevel::Basic => self.optimize(),
+            OptLevel::Full => self.eliminate_dead_code().0.fold_prefix(FOLD_STEPS),
+        };
+        optimized.eof = config.eof;
+        optimized
+    }
+
+    /// Translates each operation into an [`Ir`] without optimizing it.
+    fn lower(&self) -> Optimized {
+        let mut optimized = Optimized {
+            ir: vec![],
+            spans: vec![],
+            eof: Eof::Unchanged,
+        };
+        for (op, &span) in self.ops().iter().zip(self.spans()) {
+            let ir = match op {
+                Op::Increment => Ir::Add(1),
+                Op::Decrement => Ir::Add(255),
+                Op::Forward => Ir::Move(1),
+                Op::Backward => Ir::Move(-1),
+                Op::Open(close) => Ir::Open(*close),
+                Op::Close(open) => Ir::Close(*open),
+                Op::Out => Ir::Out,
+                Op::In => Ir::In,
+                Op::PrevTape => Ir::PrevTape,
+                Op::NextTape => Ir::NextTape,
+                Op::Random => Ir::Random,
+            };
+            optimized.push(ir, span);
+        }
+        optimized
+    }
+
+    /// Like [`optimize`](Self::optimize), but first runs as much of the
+    /// program as it can without reading input, replacing it with the
+    /// memory and output it leaves behind.
+    ///
+    /// Instructions and whole loops from the start of the program are run
+    /// until one would read input or take more than `max_steps` steps in
+    /// total. The optimized program should then be run on a new
+    /// [`State`], as the start of the program was.
+    pub fn fold_prefix(&self, max_steps: u64) -> Optimized {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("fold_prefix", max_steps).entered();
+        let ops = self.ops();
+        let mut interpreter = Interpreter::with_io(self.clone(), State::new(), Buffer::default());
+        let mut start = 0;
+        while start < ops.len() {
+            let end = match ops[start] {
+                Op::Open(close) => close + 1,
+                _ => start + 1,
+            };
+            if ops[start..end].iter().any(stops_folding) {
+                break;
+            }
+            let checkpoint = interpreter.checkpoint();
+            let written = interpreter.io().output.len();
+            while interpreter.position() < end && interpreter.steps() < max_steps {
+                if interpreter.step().is_err() {
+                    break;
+                }
+            }
+            if interpreter.position() < end {
+                interpreter.rollback(checkpoint);
+                interpreter.io_mut().output.truncate(written);
+                break;
+            }
+            interpreter.clear_checkpoints();
+            start = end;
+        }
+        if start == 0 {
+            return self.optimize();
+        }
+
+        let mut keep = vec![false; start];
+        keep.resize(ops.len(), true);
+        let mut optimized = self.retain(&keep).optimize();
+        let span = self.spans()[0].to(self.spans()[start - 1]);
+        let (state, io) = interpreter.into_parts();
+        let mut prefix = vec![Ir::Load {
+            cells: state.mem,
+            pointer: state.pointer,
+        }];
+        if !io.output.is_empty() {
+            prefix.push(Ir::Write(io.output));
+        }
+        optimized.spans.splice(0..0, prefix.iter().map(|_| span));
+        optimized.ir.splice(0..0, prefix);
+        optimized.link();
+        optimized
+    }
+
+    /// The single operation optimizing replaces the loop starting at `open`
+    /// with, if any.
+    pub(crate) fn lower_loop(&self, open: usize) -> Option<Ir> {
+        let Some(&Op::Open(close)) = self.ops().get(open) else {
+            return None;
+        };
+        // Lowering a loop which strays left of where it works would lose the
+        // move staying on the first cell.
+        let lowest = lowest(&self.ops()[open + 1..close]);
+        match self.loop_kind(open) {
+            Some(LoopKind::Clear) if lowest == 0 => Some(Ir::Clear),
+            Some(LoopKind::MulAdd(targets)) if reaches(&targets, lowest) => {
+                Some(Ir::MulAdd(targets))
+            }
+            Some(LoopKind::Scan(stride)) if lowest == stride.min(0) => Some(Ir::Scan(stride)),
+            _ => self.sweep(open).map(Ir::Sweep),
+        }
+    }
+
+    /// The stride of a loop like `[[-]>]` starting at `open`, which clears
+    /// cells until it reaches a zero cell.
+    fn sweep(&self, open: usize) -> Option<isize> {
+        let Some(&Op::Open(close)) = self.ops().get(open) else {
+            return None;
+        };
+        let body = &self.ops()[open + 1..close];
+        let (Some(Op::Open(inner)), true) = (body.first(), body.len() > 3) else {
+            return None;
+        };
+        if self.loop_kind(open + 1) != Some(LoopKind::Clear) {
+            return None;
+        }
+        let rest = &self.ops()[inner + 1..close];
+        let (by, len) = moves(rest);
+        (len == rest.len() && by != 0).then_some(by)
+    }
+}
+
+/// Combines the `>` and `<` at the start of `ops` into a single move,
+/// returning how far it moves and how many operations it covers.
+///
+/// Stops before an operation which would make the combined move end
+/// somewhere else than the operations would when they stop at the first
+/// cell, such as the `>` of `<>`.
+fn moves(ops: &[Op]) -> (isize, usize) {
+    let mut by = 0isize;
+    let mut lowest = 0isize;
+    let mut len = 0;
+    for op in ops {
+        let next = match op {
+            Op::Forward => by + 1,
+            Op::Backward => by - 1,
+            _ => break,
+        };
+        lowest = lowest.min(next);
+        if lowest < next.min(0) {
+            break;
+        }
+        by = next;
+        len += 1;
+    }
+    (by, len)
+}
+
+/// The furthest left `ops` move, relative to where they start.
+fn lowest(ops: &[Op]) -> isize {
+    let mut by = 0isize;
+    let mut lowest = 0;
+    for op in ops {
+        match op {
+            Op::Forward => by += 1,
+            Op::Backward => by -= 1,
+            _ => {}
+        }
+        lowest = lowest.min(by);
+    }
+    lowest
+}
+
+impl Optimized {
+    fn push(&mut self, ir: Ir, span: Span) {
+        self.ir.push(ir);
+        self.spans.push(span);
+    }
+
+    /// Replaces runs of clears separated by single steps, such as
+    /// `[-]>[-]>[-]`, with a [`Ir::Fill`] and a [`Ir::Move`].
+    fn fill_ranges(&mut self) {
+        let mut ir = Vec::with_capacity(self.ir.len());
+        let mut spans = Vec::with_capacity(self.spans.len());
+        let mut i = 0;
+        while i < self.ir.len() {
+            let mut len = 1;
+            let direction = match self.ir.get(i + 1) {
+                Some(&Ir::Move(step @ (1 | -1))) if self.ir[i] == Ir::Clear => step,
+                _ => 0,
+            };
+            while direction != 0
+                && self.ir.get(i + 2 * len - 1) == Some(&Ir::Move(direction))
+                && self.ir.get(i + 2 * len) == Some(&Ir::Clear)
+            {
+                len += 1;
+            }
+            if len == 1 {
+                ir.push(self.ir[i].clone());
+                spans.push(self.spans[i]);
+                i += 1;
+                continue;
+            }
+            let end = i + 2 * (len - 1);
+            let span = self.spans[i].to(self.spans[end]);
+            let by = direction * (len as isize - 1);
+            let from = by.min(0);
+            ir.push(Ir::Fill { from, len });
+            ir.push(Ir::Move(by));
+            spans.push(span);
+            spans.push(span);
+            i = end + 1;
+        }
+        self.ir = ir;
+        self.spans = spans;
+    }
+
+    /// Points each [`Ir::Open`] and [`Ir::Close`] at its partner.
+    fn link(&mut self) {
+        let mut opened = vec![];
+        for i in 0..self.ir.len() {
+            match self.ir[i] {
+                Ir::Open(_) => opened.push(i),
+                Ir::Close(_) => {
+                    let open = opened.pop().expect("brackets are balanced");
+                    self.ir[open] = Ir::Open(i);
+                    self.ir[i] = Ir::Close(open);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The operations of the optimized program.
+    pub fn ir(&self) -> &[Ir] {
+        &self.ir
+    }
+
+    /// The spans of the source each operation was lowered from, by index.
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// The number of operations in the optimized program.
+    pub fn len(&self) -> usize {
+        self.ir.len()
+    }
+
+    /// Whether the optimized program has no operations.
+    pub fn is_empty(&self) -> bool {
+        self.ir.is_empty()
+    }
+
+    /// Lowers the optimized program back into Brainfuck code, which other
+    /// interpreters can run.
+    ///
+    /// Where running the optimized program would fail with
+    /// [`RuntimeError::PointerUnderflow`], the code stays on the first cell
+    /// instead, so it may not behave like the original program.
+    pub fn to_brainfuck(&self) -> String {
+        self.to_brainfuck_with_map().0
+    }
+
+    /// Lowers the optimized program back into Brainfuck code like
+    /// [`to_brainfuck`](Self::to_brainfuck), along with a map from each
+    /// piece of the code to the source it came from.
+    pub fn to_brainfuck_with_map(&self) -> (String, SourceMap) {
+        let mut code = String::new();
+        let mut map = SourceMap(vec![]);
+        // How far right of the pointer the first cell past the loaded memory
+        // is, which is always zero.
+        let mut scratch = None;
+        // Where the last moves left which may reach the first cell end, as
+        // the pointer stays there and moving right again does not cancel
+        // them.
+        let mut floor = 0;
+        for (ir, &span) in self.ir.iter().zip(&self.spans) {
+            let mut piece = String::new();
+            match ir {
+                Ir::Add(value) => add(&mut piece, *value),
+                Ir::Move(by) => shift(&mut piece, *by),
+                Ir::Clear => piece.push_str("[-]"),
+                Ir::MulAdd(targets) => {
+                    piece.push_str("[-");
+                    update(&mut piece, targets);
+                    piece.push(']');
+                }
+                Ir::Update(targets) => update(&mut piece, targets),
+                Ir::Scan(stride) => {
+                    piece.push('[');
+                    shift(&mut piece, *stride);
+                    piece.push(']');
+                }
+                Ir::Sweep(stride) => {
+                    piece.push_str("[[-]");
+                    shift(&mut piece, *stride);
+                    piece.push(']');
+                }
+                Ir::Fill { from, len } => {
+                    shift(&mut piece, *from);
+                    piece.push_str("[-]");
+                    for _ in 1..*len {
+                        piece.push_str(">[-]");
+                    }
+                    shift(&mut piece, -(from + *len as isize - 1));
+                }
+                Ir::Open(_) => piece.push('['),
+                Ir::Close(_) => piece.push(']'),
+                Ir::Load { cells, pointer } => {
+                    let mut at = 0;
+                    for (i, &cell) in cells.iter().enumerate() {
+                        if cell != 0 {
+                            shift(&mut piece, i as isize - at);
+                            add(&mut piece, cell);
+                            at = i as isize;
+                        }
+                    }
+                    shift(&mut piece, *pointer as isize - at);
+                    scratch = Some(cells.len() as isize - *pointer as isize);
+                }
+                Ir::Write(bytes) => {
+                    let scratch = scratch.expect("writes follow a load");
+                    shift(&mut piece, scratch);
+                    let mut cell = 0u8;
+                    for &b in bytes {
+                        add(&mut piece, b.wrapping_sub(cell));
+                        piece.push('.');
+                        cell = b;
+                    }
+                    piece.push_str("[-]");
+                    shift(&mut piece, -scratch);
+                }
+                Ir::Out => piece.push('.'),
+                Ir::In => piece.push(','),
+                Ir::PrevTape => piece.push('{'),
+                Ir::NextTape => piece.push('}'),
+                Ir::Random => piece.push('?'),
+            }
+            append(&mut code, &mut map, &piece, span, floor);
+            if matches!(ir, Ir::Move(by) if *by < 0) {
+                floor = code.len();
+            }
+        }
+        (code, map)
+    }
+
+    /// Runs the optimized program to completion on a state, reading from
+    /// and writing to `io`.
+    pub fn run(&self, state: &mut State, io: &mut impl Io) -> Result<(), RuntimeError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("run", ir = self.len()).entered();
+        let mut position = 0;
+        while let Some(ir) = self.ir.get(position) {
+            match ir {
+                Ir::Add(value) => {
+                    let cell = state.cell_mut();
+                    *cell = cell.wrapping_add(*value);
+                }
+                Ir::Move(by) => move_by(state, *by),
+                Ir::Clear => *state.cell_mut() = 0,
+                Ir::MulAdd(targets) => {
+                    let value = state.cell();
+                    if value != 0 {
+                        for &(offset, factor) in targets {
+                            let cell = cell_at(state, offset)?;
+                            *cell = cell.wrapping_add(value.wrapping_mul(factor));
+                        }
+                        *state.cell_mut() = 0;
+                    }
+                }
+                Ir::Update(targets) => {
+                    for &(offset, value) in targets {
+                        let cell = cell_at(state, offset)?;
+                        *cell = cell.wrapping_add(value);
+                    }
+                }
+                Ir::Scan(stride) => scan(state, *stride)?,
+                Ir::Sweep(stride) => sweep(state, *stride),
+                Ir::Fill { from, len } => {
+                    let start = offset(state, *from)?;
+                    let end = start + len;
+                    if end > state.mem.len() {
+                        state.mem.resize(end, 0);
+                    }
+                    state.mem[start..end].fill(0);
+                }
+                Ir::Open(close) => {
+                    if state.cell() == 0 {
+                        position = *close;
+                    }
+                }
+                Ir::Close(open) => {
+                    if state.cell() != 0 {
+                        position = *open;
+                    }
+                }
+                Ir::Load { cells, pointer } => {
+                    state.mem.clone_from(cells);
+                    state.pointer = *pointer;
+                    state.cell_mut();
+                }
+                Ir::Write(bytes) => {
+                    for &b in bytes {
+                        io.write(b)?;
+                    }
+                    state.outted = true;
+                    state.written += bytes.len();
+                }
+                Ir::Out => state.output(io)?,
+                Ir::In => {
+                    if state.input(io)?.is_none() {
+                        let cell = state.cell_mut();
+                        *cell = self.eof.value(*cell);
+                    }
+                }
+                Ir::PrevTape => state.prev_tape(),
+                Ir::NextTape => state.next_tape(),
+                Ir::Random => *state.cell_mut() = state.random(),
+            }
+            position += 1;
+        }
+        Ok(io.flush()?)
+    }
+}
+
+/// The location of the cell at an offset from the pointer.
+fn offset(state: &State, offset: isize) -> Result<usize, RuntimeError> {
+    state
+        .pointer
+        .checked_add_signed(offset)
+        .ok_or(RuntimeError::PointerUnderflow)
+}
+
+/// Writes the shortest run of `+` or `-` which adds `value` to a cell.
+fn add(code: &mut String, value: u8) {
+    let (c, n) = if value <= 128 {
+        ('+', value)
+    } else {
+        ('-', value.wrapping_neg())
+    };
+    code.extend(iter::repeat_n(c, n.into()));
+}
+
+/// Whether [`Program::fold_prefix`] stops before an operation, as it reads
+/// input, or as only the selected tape is loaded and not the generator.
+pub(crate) fn stops_folding(op: &Op) -> bool {
+    matches!(op, Op::In | Op::PrevTape | Op::NextTape | Op::Random)
+}
+
+/// Writes a piece of code for the operations at `span`, merging the moves it
+/// starts with into those the code ends with so that no `><` or `<>` is
+/// left between them.
+///
+/// No moves before `floor` are merged.
+fn append(code: &mut String, map: &mut SourceMap, piece: &str, span: Span, floor: usize) {
+    let is_move = |c| c == '<' || c == '>';
+    let lead = piece.len() - piece.trim_start_matches(is_move).len();
+    let (mut start, mut rest) = (code.len(), piece);
+    if lead > 0 {
+        start = code.trim_end_matches(is_move).len().max(floor);
+        let by = distance(&code[start..]) + distance(&piece[..lead]);
+        code.truncate(start);
+        while let Some(last) = map.0.last_mut() {
+            if last.generated.end <= start {
+                break;
+            }
+            last.generated.end = start;
+            if !last.generated.is_empty() {
+                break;
+            }
+            map.0.pop();
+        }
+        shift(code, by);
+        rest = &piece[lead..];
+    }
+    code.push_str(rest);
+    if code.len() > start {
+        map.0.push(Mapping {
+            generated: start..code.len(),
+            original: span,
+        });
+    }
+}
+
+/// How far a run of `>` and `<` moves the pointer.
+fn distance(code: &str) -> isize {
+    code.chars().map(|c| if c == '>' { 1 } else { -1 }).sum()
+}
+
+/// Writes the run of `>` or `<` which moves the pointer `by` cells.
+fn shift(code: &mut String, by: isize) {
+    let c = if by < 0 { '<' } else { '>' };
+    code.extend(iter::repeat_n(c, by.unsigned_abs()));
+}
+
+/// Writes code which adds to the cells at each offset, returning to the
+/// cell it started on.
+fn update(code: &mut String, targets: &[(isize, u8)]) {
+    let mut at = 0;
+    for &(offset, value) in targets {
+        shift(code, offset - at);
+        add(code, value);
+        at = offset;
+    }
+    shift(code, -at);
+}
+
+/// The cell at an offset from the pointer, growing the memory to reach it
+/// if needed.
+fn cell_at(state: &mut State, offset_by: isize) -> Result<&mut u8, RuntimeError> {
+    let index = offset(state, offset_by)?;
+    if index >= state.mem.len() {
+        state.mem.resize(index + 1, 0);
+    }
+    Ok(&mut state.mem[index])
+}
+
+/// Moves the pointer, stopping at the first cell like `<` does.
+fn move_by(state: &mut State, by: isize) {
+    state.pointer = state.pointer.saturating_add_signed(by);
+    state.cell_mut();
+}
+
+fn scan(state: &mut State, stride: isize) -> Result<(), RuntimeError> {
+    if stride == 1 && state.cell() != 0 {
+        let start = state.pointer;
+        let found = state.mem[start..].iter().position(|&cell| cell == 0);
+        state.pointer = start + found.unwrap_or(state.mem.len() - start);
+        state.cell_mut();
+        return Ok(());
+    }
+    while state.cell() != 0 {
+        state.pointer = offset(state, stride)?;
+        state.cell_mut();
+    }
+    Ok(())
+}
+
+fn sweep(state: &mut State, stride: isize) {
+    match stride {
+        1 if state.pointer < state.mem.len() => {
+            let start = state.pointer;
+            let len = state.mem[start..].iter().position(|&cell| cell == 0);
+            let len = len.unwrap_or(state.mem.len() - start);
+            state.mem[start..start + len].fill(0);
+            state.pointer += len;
+            state.cell_mut();
+        }
+        -1 if state.pointer < state.mem.len() => {
+            let end = state.pointer + 1;
+            // Stopping at the first cell leaves the pointer there once it
+            // is cleared, ending the loop.
+            let start = state.mem[..end].iter().rposition(|&cell| cell == 0);
+            match start {
+                Some(zero) => {
+                    state.mem[zero + 1..end].fill(0);
+                    state.pointer = zero;
+                }
+                None => {
+                    state.mem[..end].fill(0);
+                    state.pointer = 0;
+                }
+            }
+        }
+        _ => {
+            while state.cell() != 0 {
+                *state.cell_mut() = 0;
+                move_by(state, stride);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Programs along with their input, covering each kind of loop the
+    /// optimizer replaces.
+    const PROGRAMS: &[(&str, &str)] = &[
+        (
+            "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.",
+            "",
+        ),
+        (",[.[-],]", "echo"),
+        (",[->+>+++<<]>.>.", "\x07"),
+        ("+>+>+<<[>]+.<<.", ""),
+        ("+>+>+>+[[-]<]>>>>.", ""),
+        (">+>+>+<<[-]>[-]>[-]+.<<<.", ""),
+        ("[-][.,]++.", ""),
+        (",,,+.", "a"),
+        ("++++++[>++++++++<-]>+.,.[-]++.", "x"),
+        ("+++++[>+++<-]>[>+>++<<-]>>[<+>-]<<,[-<+>]<.", "\x02"),
+    ];
+
+    /// The output, memory without its trailing zeros, and pointer the
+    /// interpreter leaves.
+    fn reference(program: &Program, input: &str) -> (Vec<u8>, Vec<u8>, usize) {
+        let mut interpreter = Interpreter::with_io(
+            program.clone(),
+            State::new(),
+            Buffer::new(input.as_bytes().to_vec()),
+        );
+        interpreter.limit_steps(1_000_000);
+        interpreter.run().unwrap();
+        let (state, io) = interpreter.into_parts();
+        (io.output, trimmed(&state.mem), state.pointer)
+    }
+
+    fn optimized(optimized: &Optimized, input: &str) -> (Vec<u8>, Vec<u8>, usize) {
+        let mut state = State::new();
+        let mut io = Buffer::new(input.as_bytes().to_vec());
+        optimized.run(&mut state, &mut io).unwrap();
+        (io.output, trimmed(&state.mem), state.pointer)
+    }
+
+    fn trimmed(mem: &[u8]) -> Vec<u8> {
+        let len = mem.iter().rposition(|&cell| cell != 0).map_or(0, |i| i + 1);
+        mem[..len].to_vec()
+    }
+
+    #[test]
+    fn optimizing_keeps_what_programs_do() {
+        for &(src, input) in PROGRAMS {
+            let program = Program::parse(src).unwrap();
+            let expected = reference(&program, input);
+            assert_eq!(optimized(&program.optimize(), input), expected, "{src}");
+            let full = Config {
+                opt_level: OptLevel::Full,
+                ..Config::default()
+            };
+            assert_eq!(
+                optimized(&program.optimize_with(&full), input),
+                expected,
+                "{src}"
+            );
+        }
+    }
+
+    #[test]
+    fn folding_keeps_what_programs_do() {
+        for &(src, input) in PROGRAMS {
+            let program = Program::parse(src).unwrap();
+            let expected = reference(&program, input);
+            for max_steps in [0, 1, 10, 100, FOLD_STEPS] {
+                let folded = program.fold_prefix(max_steps);
+                assert_eq!(
+                    optimized(&folded, input),
+                    expected,
+                    "{src} in {max_steps} steps"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn folding_stops_before_input() {
+        let folded = Program::parse("+++.,.").unwrap().fold_prefix(FOLD_STEPS);
+        assert_eq!(
+            folded.ir()[..2],
+            [
+                Ir::Load {
+                    cells: vec![3],
+                    pointer: 0
+                },
+                Ir::Write(vec![3]),
+            ]
+        );
+        assert_eq!(folded.ir()[2..], [Ir::In, Ir::Out]);
+    }
+
+    #[test]
+    fn eliminating_dead_code_keeps_what_programs_do() {
+        for &(src, input) in PROGRAMS {
+            let program = Program::parse(src).unwrap();
+            let (live, _) = program.eliminate_dead_code();
+            let (output, _, pointer) = reference(&program, input);
+            let (live_output, _, live_pointer) = reference(&live, input);
+            assert_eq!((live_output, live_pointer), (output, pointer), "{src}");
+        }
+    }
+
+    #[test]
+    fn eliminates_loops_which_never_run() {
+        let (live, removed) = Program::parse("[-][.,]++.").unwrap().eliminate_dead_code();
+        assert_eq!(live.ops(), [Op::Increment, Op::Increment, Op::Out]);
+        assert_eq!(removed.len(), 2);
+        assert!(removed.iter().all(|d| d.lint == Lint::DeadLoop));
+    }
+
+    #[test]
+    fn lowered_code_keeps_what_programs_do() {
+        for &(src, input) in PROGRAMS {
+            let program = Program::parse(src).unwrap();
+            let (output, _, pointer) = reference(&program, input);
+            let code = program.optimize().to_brainfuck();
+            let lowered = Program::parse(&code).unwrap();
+            let (lowered_output, _, lowered_pointer) = reference(&lowered, input);
+            assert_eq!(
+                (lowered_output, lowered_pointer),
+                (output, pointer),
+                "{src} => {code}"
+            );
+            assert!(!code.contains("<>") && !code.contains("><"), "{code}");
+        }
+    }
+
+    #[test]
+    fn lowered_code_maps_back_to_the_source() {
+        let (code, map) = Program::parse(",>[-]>[-]>[-]>>.")
+            .unwrap()
+            .optimize()
+            .to_brainfuck_with_map();
+        assert_eq!(code, ",>[-]>[-]>[-]>>.");
+        let fill = map.lookup(4).unwrap();
+        assert_eq!((fill.start, fill.end), (2, 13));
+        let out = map.lookup(15).unwrap();
+        assert_eq!((out.start, out.end), (15, 16));
+        assert_eq!(map.lookup(16), None);
+    }
+}
@@ -0,0 +1,451 @@
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::{ErrorKind, Read};
+
+#[cfg(feature = "std")]
+use crate::Error;
+use crate::{Config, ParseError};
+
+/// The location of an instruction in its source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    /// Byte offset of the first character.
+    pub start: usize,
+    /// Byte offset just past the last character.
+    pub end: usize,
+    /// Line of the first character, starting at 1.
+    pub line: usize,
+    /// Column of the first character, starting at 1.
+    pub column: usize,
+}
+
+impl Span {
+    /// Creates a span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        let (first, last) = if self.start <= other.start {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        Span {
+            start: first.start,
+            end: first.end.max(last.end),
+            line: first.line,
+            column: first.column,
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Iterates over the characters of `raw` along with their spans.
+pub(crate) fn chars_with_spans(raw: &str) -> impl Iterator<Item = (char, Span)> + '_ {
+    let mut line = 1;
+    let mut column = 1;
+    raw.char_indices().map(move |(start, c)| {
+        let span = Span {
+            start,
+            end: start + c.len_utf8(),
+            line,
+            column,
+        };
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+        (c, span)
+    })
+}
+
+/// Builds a program from its source a piece at a time.
+struct Parser {
+    ops: Vec<Op>,
+    spans: Vec<Span>,
+    opened: Vec<usize>,
+    /// The byte offset of the next byte.
+    offset: usize,
+    /// The line of the next character, starting at 0.
+    line: usize,
+    /// The column of the next character, starting at 0.
+    column: usize,
+    /// Whether a leading `#!` line is ignored.
+    allow_shebang: bool,
+    /// Whether `{` and `}` switch tapes.
+    tapes: bool,
+    /// Whether `?` writes a random byte.
+    random: bool,
+    /// Whether everything read so far could be a `#!` line, whose commands
+    /// are ignored.
+    shebang: bool,
+}
+
+impl Parser {
+    fn new(config: &Config) -> Self {
+        Self {
+            ops: vec![],
+            spans: vec![],
+            opened: vec![],
+            offset: 0,
+            line: 0,
+            column: 0,
+            allow_shebang: config.shebang,
+            tapes: config.tapes > 1,
+            random: config.random.is_some(),
+            shebang: false,
+        }
+    }
+
+    fn feed(&mut self, bytes: &[u8]) -> Result<(), ParseError> {
+        for &b in bytes {
+            let span = Span {
+                start: self.offset,
+                end: self.offset + 1,
+                line: self.line + 1,
+                column: self.column + 1,
+            };
+            self.shebang = match span.start {
+                0 => self.allow_shebang && b == b'#',
+                1 => self.shebang && b == b'!',
+                _ => self.shebang && b != b'\n',
+            };
+            self.offset += 1;
+            match b {
+                b'\n' => {
+                    self.line += 1;
+                    self.column = 0;
+                }
+                // The rest of a multi-byte character takes up no column.
+                _ if b & 0xC0 == 0x80 => {}
+                _ => self.column += 1,
+            }
+            if self.shebang {
+                continue;
+            }
+            let op = match b {
+                b'+' => Op::Increment,
+                b'-' => Op::Decrement,
+                b'>' => Op::Forward,
+                b'<' => Op::Backward,
+                b'[' => {
+                    self.opened.push(self.ops.len());
+                    // Patched once the matching `]` is found.
+                    Op::Open(0)
+                }
+                b']' => match self.opened.pop() {
+                    Some(open) => {
+                        self.ops[open] = Op::Open(self.ops.len());
+                        Op::Close(open)
+                    }
+                    None => return Err(ParseError::UnmatchedClose(span)),
+                },
+                b'.' => Op::Out,
+                b',' => Op::In,
+                b'{' if self.tapes => Op::PrevTape,
+                b'}' if self.tapes => Op::NextTape,
+                b'?' if self.random => Op::Random,
+                _ => continue,
+            };
+            self.ops.push(op);
+            self.spans.push(span);
+        }
+        Ok(())
+    }
+
+    fn finish(mut self, source: &str) -> Result<Program, ParseError> {
+        if let Some(open) = self.opened.pop() {
+            return Err(ParseError::UnmatchedOpen(self.spans[open]));
+        }
+        Ok(Program {
+            source: source.into(),
+            ops: self.ops.into(),
+            spans: self.spans.into(),
+        })
+    }
+}
+
+/// A single operation of a [`Program`].
+///
+/// Unlike [`Instruction`](crate::Instruction), loops are flattened into a pair of
+/// jumps, so a program can be stepped through one operation at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Represents the `+` instruction.
+    Increment,
+    /// Represents the `-` instruction.
+    Decrement,
+    /// Represents the `>` instruction.
+    Forward,
+    /// Represents the `<` instruction.
+    Backward,
+    /// Represents the `[` instruction.
+    ///
+    /// Holds the index of the matching [`Op::Close`], which execution
+    /// skips past when the current cell is zero.
+    Open(usize),
+    /// Represents the `]` instruction.
+    ///
+    /// Holds the index of the matching [`Op::Open`], which execution
+    /// returns to when the current cell is not zero.
+    Close(usize),
+    /// Represents the `.` instruction.
+    Out,
+    /// Represents the `,` instruction.
+    In,
+    /// Represents the `{` instruction of programs with several tapes,
+    /// which selects the previous tape.
+    PrevTape,
+    /// Represents the `}` instruction of programs with several tapes,
+    /// which selects the next tape.
+    NextTape,
+    /// Represents the `?` instruction of programs with a random seed, which
+    /// writes a pseudo-random byte to the cell.
+    Random,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let c = match self {
+            Op::Increment => '+',
+            Op::Decrement => '-',
+            Op::Forward => '>',
+            Op::Backward => '<',
+            Op::Open(_) => '[',
+            Op::Close(_) => ']',
+            Op::Out => '.',
+            Op::In => ',',
+            Op::PrevTape => '{',
+            Op::NextTape => '}',
+            Op::Random => '?',
+        };
+        write!(f, "{c}")
+    }
+}
+
+/// A parsed Brainfuck program which remembers where each of
+/// its operations came from.
+///
+/// Clones share the parsed operations, so one program can be run by many
+/// threads at once, each with its own [`State`](crate::State), without
+/// parsing or copying it again.
+#[derive(Debug, Clone)]
+pub struct Program {
+    source: Arc<str>,
+    ops: Arc<[Op]>,
+    spans: Arc<[Span]>,
+}
+
+impl Program {
+    /// Parses raw Brainfuck code into a program.
+    ///
+    /// Ignores characters not in the Brainfuck language, along with a
+    /// leading `#!` line, but fails on brackets without a partner.
+    pub fn parse(raw: &str) -> Result<Self, ParseError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("parse", len = raw.len()).entered();
+        Self::parse_with(raw, &Config::default())
+    }
+
+    /// Creates a program from operations parsed by the `brainfuck!` macro.
+    #[doc(hidden)]
+    pub fn __from_parts(source: &str, ops: &[Op], spans: &[Span]) -> Self {
+        Self {
+            source: source.into(),
+            ops: ops.into(),
+            spans: spans.into(),
+        }
+    }
+
+    /// Parses raw Brainfuck code into a program, following the dialect
+    /// chosen by `config`.
+    pub fn parse_with(raw: &str, config: &Config) -> Result<Self, ParseError> {
+        let mut parser = Parser::new(config);
+        parser.feed(raw.as_bytes())?;
+        parser.finish(raw)
+    }
+
+    /// Parses Brainfuck code from raw bytes, which do not need to be valid
+    /// UTF-8, skipping every byte which is not a command.
+    ///
+    /// The source is only kept if it is valid UTF-8, otherwise
+    /// [`source`](Self::source) is empty, but spans still point into `raw`.
+    pub fn parse_bytes(raw: &[u8]) -> Result<Self, ParseError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("parse_bytes", len = raw.len()).entered();
+        let mut parser = Parser::new(&Config::default());
+        parser.feed(raw)?;
+        let source = core::str::from_utf8(raw).unwrap_or_default();
+        parser.finish(source)
+    }
+
+    /// Parses Brainfuck code as it is read, without holding all of it in
+    /// memory at once.
+    ///
+    /// The code does not need to be valid UTF-8, as only the bytes of
+    /// commands are looked at. The source is not kept, so
+    /// [`source`](Self::source) is empty, but spans still point into what
+    /// was read.
+    #[cfg(feature = "std")]
+    pub fn parse_from_reader(reader: impl Read) -> Result<Self, Error> {
+        Self::parse_from_reader_with(reader, &Config::default())
+    }
+
+    /// Parses Brainfuck code as it is read, like
+    /// [`parse_from_reader`](Self::parse_from_reader), following the dialect
+    /// chosen by `config`.
+    #[cfg(feature = "std")]
+    pub fn parse_from_reader_with(mut reader: impl Read, config: &Config) -> Result<Self, Error> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("parse_from_reader").entered();
+        let mut parser = Parser::new(config);
+        let mut chunk = vec![0; 64 * 1024];
+        loop {
+            match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(len) => parser.feed(&chunk[..len])?,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(parser.finish("")?)
+    }
+
+    /// The source code the program was parsed from.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The operations of the program.
+    pub fn ops(&self) -> &[Op] {
+        &self.ops
+    }
+
+    /// The spans of the program's operations, by index.
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// The number of operations in the program.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Whether the program has no operations.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// A program with only the operations `keep` is true for, which must
+    /// keep or remove each bracket along with its partner.
+    pub(crate) fn retain(&self, keep: &[bool]) -> Program {
+        let mut ops = vec![];
+        let mut spans = vec![];
+        let mut opened = vec![];
+        for (i, &op) in self.ops.iter().enumerate() {
+            if !keep[i] {
+                continue;
+            }
+            let op = match op {
+                Op::Open(_) => {
+                    opened.push(ops.len());
+                    Op::Open(0)
+                }
+                Op::Close(_) => {
+                    let open = opened.pop().expect("brackets are kept in pairs");
+                    ops[open] = Op::Open(ops.len());
+                    Op::Close(open)
+                }
+                op => op,
+            };
+            ops.push(op);
+            spans.push(self.spans[i]);
+        }
+        Program {
+            source: self.source.clone(),
+            ops: ops.into(),
+            spans: spans.into(),
+        }
+    }
+
+    /// The index of the first operation at or after a line and column of
+    /// the source.
+    pub fn position_at(&self, line: usize, column: usize) -> Option<usize> {
+        self.spans
+            .iter()
+            .position(|span| (span.line, span.column) >= (line, column))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(start: usize, line: usize, column: usize) -> Span {
+        Span {
+            start,
+            end: start + 1,
+            line,
+            column,
+        }
+    }
+
+    #[test]
+    fn matches_brackets_and_ignores_comments() {
+        let program = Program::parse("+ a [->\n<] b").unwrap();
+        assert_eq!(
+            program.ops(),
+            [
+                Op::Increment,
+                Op::Open(5),
+                Op::Decrement,
+                Op::Forward,
+                Op::Backward,
+                Op::Close(1),
+            ]
+        );
+        assert_eq!(program.source(), "+ a [->\n<] b");
+    }
+
+    #[test]
+    fn spans_count_bytes_lines_and_columns() {
+        let program = Program::parse("#!/bin/bf\n+\n  [é]").unwrap();
+        assert_eq!(
+            program.spans(),
+            [span(10, 2, 1), span(14, 3, 3), span(17, 3, 5)]
+        );
+        assert_eq!(program.position_at(3, 1), Some(1));
+        assert_eq!(program.position_at(4, 1), None);
+    }
+
+    #[test]
+    fn reports_unmatched_brackets() {
+        assert_eq!(
+            Program::parse("[[]").unwrap_err(),
+            ParseError::UnmatchedOpen(span(0, 1, 1))
+        );
+        assert_eq!(
+            Program::parse("+\n+]").unwrap_err(),
+            ParseError::UnmatchedClose(span(3, 2, 2))
+        );
+        assert_eq!(
+            Program::parse("]").unwrap_err().to_string(),
+            "1:1: `]` has no matching `[`."
+        );
+    }
+
+    #[test]
+    fn parses_bytes_which_are_not_utf8() {
+        let program = Program::parse_bytes(b"\xff+\xfe.").unwrap();
+        assert_eq!(program.ops(), [Op::Increment, Op::Out]);
+        assert_eq!(program.source(), "");
+        assert_eq!(program.spans()[1], span(3, 1, 4));
+    }
+}
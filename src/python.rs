@@ -0,0 +1,134 @@
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::{Buffer, Chain, Interpreter, ParseError, Program, RuntimeError};
+
+fn parse_error(e: ParseError) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+fn runtime_error(e: RuntimeError) -> PyErr {
+    PyRuntimeError::new_err(Chain(&e).to_string())
+}
+
+/// Brainfuck code parsed by `parse`.
+#[pyclass(name = "Program", module = "headaches", frozen)]
+struct PyProgram(Program);
+
+#[pymethods]
+impl PyProgram {
+    /// The number of operations in the program.
+    fn __len__(&self) -> usize {
+        self.0.len()
+    }
+
+    /// The code the program was parsed from.
+    #[getter]
+    fn source(&self) -> &str {
+        self.0.source()
+    }
+
+    /// The common mistakes in the program, each with its line and column.
+    fn lint(&self) -> Vec<String> {
+        self.0.lint().iter().map(ToString::to_string).collect()
+    }
+}
+
+/// Parses Brainfuck code, raising `ValueError` if a bracket has no partner.
+#[pyfunction]
+fn parse(src: &str) -> PyResult<PyProgram> {
+    Program::parse(src).map(PyProgram).map_err(parse_error)
+}
+
+/// Runs Brainfuck code on `input` to completion, returning what it wrote.
+///
+/// Raises `ValueError` if the code cannot be parsed and `RuntimeError` if
+/// running it fails, such as taking more than `max_steps` steps.
+#[pyfunction]
+#[pyo3(signature = (src, input = None, max_steps = None))]
+fn run<'py>(
+    py: Python<'py>,
+    src: &str,
+    input: Option<&[u8]>,
+    max_steps: Option<u64>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let mut interpreter = PyInterpreter::new(src, input, max_steps)?;
+    interpreter.run()?;
+    Ok(interpreter.output(py))
+}
+
+/// Steps through Brainfuck code one operation at a time, reading from
+/// `input` and keeping what it writes.
+#[pyclass(name = "Interpreter", module = "headaches", unsendable)]
+struct PyInterpreter(Interpreter<Buffer>);
+
+#[pymethods]
+impl PyInterpreter {
+    #[new]
+    #[pyo3(signature = (src, input = None, max_steps = None))]
+    fn new(src: &str, input: Option<&[u8]>, max_steps: Option<u64>) -> PyResult<Self> {
+        let program = Program::parse(src).map_err(parse_error)?;
+        let mut builder = Interpreter::builder().input(input.unwrap_or_default());
+        if let Some(max_steps) = max_steps {
+            builder = builder.max_steps(max_steps);
+        }
+        Ok(Self(builder.build(program)))
+    }
+
+    /// Executes the next operation, if the program has not halted.
+    fn step(&mut self) -> PyResult<()> {
+        self.0.step().map_err(runtime_error)
+    }
+
+    /// Executes the program until it halts.
+    fn run(&mut self) -> PyResult<()> {
+        self.0.run().map_err(runtime_error)
+    }
+
+    /// Whether the program has run to its end.
+    #[getter]
+    fn halted(&self) -> bool {
+        self.0.is_halted()
+    }
+
+    /// The index of the next operation to execute.
+    #[getter]
+    fn position(&self) -> usize {
+        self.0.position()
+    }
+
+    /// The number of operations executed so far.
+    #[getter]
+    fn steps(&self) -> u64 {
+        self.0.steps()
+    }
+
+    /// The location of the pointer.
+    #[getter]
+    fn pointer(&self) -> usize {
+        self.0.state().pointer
+    }
+
+    /// Every cell of the memory used so far.
+    #[getter]
+    fn memory<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.state().mem)
+    }
+
+    /// The bytes written so far.
+    #[getter]
+    fn output<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.io().output)
+    }
+}
+
+/// The `headaches` Python module.
+#[pymodule]
+fn headaches(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(parse, m)?)?;
+    m.add_function(wrap_pyfunction!(run, m)?)?;
+    m.add_class::<PyProgram>()?;
+    m.add_class::<PyInterpreter>()?;
+    Ok(())
+}
@@ -0,0 +1,172 @@
+use alloc::collections::VecDeque;
+#[cfg(feature = "std")]
+use std::io::Write;
+
+use crate::{Io, IoError, LogError};
+
+/// Wraps an [`Io`], logging every byte read from it so the input can be
+/// given again with [`Replay`].
+///
+/// Each line of the log holds one byte in hexadecimal, followed by the
+/// character it represents if it is printable ASCII, such as `48 H`. Each
+/// read past the end of input is logged as `eof`.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct Record<I: Io, W: Write> {
+    io: I,
+    log: W,
+}
+
+#[cfg(feature = "std")]
+impl<I: Io, W: Write> Record<I, W> {
+    /// Creates an `Io` which reads from and writes to `io`, writing the
+    /// bytes read to `log`.
+    pub fn new(io: I, log: W) -> Self {
+        Self { io, log }
+    }
+
+    /// Consumes the recorder, returning the `Io` and the log.
+    pub fn into_inner(self) -> (I, W) {
+        (self.io, self.log)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<I: Io, W: Write> Io for Record<I, W> {
+    fn read(&mut self) -> Result<Option<u8>, IoError> {
+        let byte = self.io.read()?;
+        match byte {
+            Some(b) if b.is_ascii_graphic() => writeln!(self.log, "{b:02x} {}", b as char)?,
+            Some(b) => writeln!(self.log, "{b:02x}")?,
+            None => writeln!(self.log, "eof")?,
+        }
+        Ok(byte)
+    }
+    fn write(&mut self, byte: u8) -> Result<(), IoError> {
+        self.io.write(byte)
+    }
+    fn flush(&mut self) -> Result<(), IoError> {
+        self.log.flush()?;
+        self.io.flush()
+    }
+}
+
+/// Wraps an [`Io`], reading the input from a log written by [`Record`]
+/// before reading from the `Io` itself.
+///
+/// If the log ends with `eof`, the `Io` is never read from.
+#[derive(Debug, Clone)]
+pub struct Replay<I: Io> {
+    io: I,
+    input: VecDeque<u8>,
+    eof: bool,
+}
+
+impl<I: Io> Replay<I> {
+    /// Creates an `Io` which reads the bytes in `log`, then from `io`, and
+    /// writes to `io`.
+    ///
+    /// Each line holds a byte in hexadecimal, optionally followed by a space
+    /// and any note, or `eof`. Once there is an `eof`, only more `eof`
+    /// lines may follow. Blank lines and lines starting with `#` are
+    /// ignored.
+    pub fn new(log: &str, io: I) -> Result<Self, LogError> {
+        let mut input = VecDeque::new();
+        let mut eof = false;
+        for (i, line) in log.lines().enumerate() {
+            let line = line.trim_start();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let word = line.split(' ').next().unwrap_or_default();
+            if word == "eof" {
+                eof = true;
+            } else if eof {
+                return Err(LogError::InvalidLine(i + 1));
+            } else {
+                let b = u8::from_str_radix(word, 16).map_err(|_| LogError::InvalidLine(i + 1))?;
+                input.push_back(b);
+            }
+        }
+        Ok(Self { io, input, eof })
+    }
+}
+
+impl<I: Io> Io for Replay<I> {
+    fn read(&mut self) -> Result<Option<u8>, IoError> {
+        match self.input.pop_front() {
+            Some(b) => Ok(Some(b)),
+            None if self.eof => Ok(None),
+            None => self.io.read(),
+        }
+    }
+    fn write(&mut self, byte: u8) -> Result<(), IoError> {
+        self.io.write(byte)
+    }
+    fn flush(&mut self) -> Result<(), IoError> {
+        self.io.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Buffer, Interpreter, Program, State};
+
+    /// Runs a program, returning what it wrote and the `Io` it was given.
+    fn run<I: Io>(code: &str, io: I) -> (Vec<u8>, I) {
+        let program = Program::parse(code).unwrap();
+        let mut interpreter =
+            Interpreter::with_io(program, State::new(), Capture { io, output: vec![] });
+        interpreter.run().unwrap();
+        let (_, capture) = interpreter.into_parts();
+        (capture.output, capture.io)
+    }
+
+    /// Keeps what a program writes, while reading from another `Io`.
+    struct Capture<I> {
+        io: I,
+        output: Vec<u8>,
+    }
+
+    impl<I: Io> Io for Capture<I> {
+        fn read(&mut self) -> Result<Option<u8>, IoError> {
+            self.io.read()
+        }
+        fn write(&mut self, byte: u8) -> Result<(), IoError> {
+            self.output.push(byte);
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn replays_what_was_recorded() {
+        let code = ",.,.,.+.,.";
+        let (output, record) = run(code, Record::new(Buffer::new(*b"H\n"), vec![]));
+        let log = String::from_utf8(record.into_inner().1).unwrap();
+        assert_eq!(log, "48 H\n0a\neof\neof\n");
+        let (replayed, _) = run(code, Replay::new(&log, Buffer::new(*b"unread")).unwrap());
+        assert_eq!(replayed, output);
+    }
+
+    #[test]
+    fn reads_on_once_the_log_runs_out() {
+        let log = "# the first byte\n41 A\n\n42";
+        let (output, _) = run(",.,.,.", Replay::new(log, Buffer::new(*b"C")).unwrap());
+        assert_eq!(output, b"ABC");
+    }
+
+    #[test]
+    fn rejects_bytes_after_the_end_of_input() {
+        assert!(Replay::new("41\neof\neof", Buffer::default()).is_ok());
+        assert_eq!(
+            Replay::new("41\neof\n42", Buffer::default()).unwrap_err(),
+            LogError::InvalidLine(3)
+        );
+        assert_eq!(
+            Replay::new("4x", Buffer::default()).unwrap_err(),
+            LogError::InvalidLine(1)
+        );
+    }
+}
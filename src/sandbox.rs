@@ -0,0 +1,170 @@
+use std::fmt;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{Interpreter, Io, IoError, ParseError, Program, RuntimeError};
+
+/// What a program run by [`run`] is allowed to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SandboxLimits {
+    /// The number of steps after which the program is stopped.
+    pub max_steps: u64,
+    /// The number of cells the program may use.
+    pub max_memory: usize,
+    /// The number of bytes the program may write.
+    pub max_output: usize,
+    /// How long the program may run for.
+    pub timeout: Duration,
+}
+
+impl Default for SandboxLimits {
+    /// Ten million steps, a million cells, a mebibyte of output, and a
+    /// second of time.
+    fn default() -> Self {
+        Self {
+            max_steps: 10_000_000,
+            max_memory: 1 << 20,
+            max_output: 1 << 20,
+            timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+/// How running a program with [`run`] went.
+#[derive(Debug)]
+pub struct SandboxResult {
+    /// The bytes the program wrote, even if it failed.
+    pub output: Vec<u8>,
+    /// The number of steps executed.
+    pub steps: u64,
+    /// How long the program ran for.
+    pub elapsed: Duration,
+    /// Whether the program halted, or why it did not.
+    pub result: Result<(), SandboxError>,
+}
+
+/// Why a program run by [`run`] did not halt.
+#[derive(Debug)]
+pub enum SandboxError {
+    /// The program could not be parsed.
+    Parse(ParseError),
+    /// The program failed while running, such as by reaching the step or
+    /// memory limit.
+    Runtime(RuntimeError),
+    /// The program tried to write more than the given number of bytes.
+    OutputLimit(usize),
+    /// The program ran for longer than it was allowed.
+    Timeout(Duration),
+}
+
+impl fmt::Display for SandboxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SandboxError::Parse(_) => write!(f, "Could not parse the program"),
+            SandboxError::Runtime(_) => write!(f, "The program failed"),
+            SandboxError::OutputLimit(bytes) => {
+                write!(f, "The program tried to write more than {bytes} bytes.")
+            }
+            SandboxError::Timeout(timeout) => {
+                write!(f, "The program did not halt within {timeout:?}.")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SandboxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SandboxError::Parse(e) => Some(e),
+            SandboxError::Runtime(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Gives a program its input and keeps what it writes, up to a limit.
+struct Captured {
+    input: Vec<u8>,
+    read: usize,
+    output: Vec<u8>,
+    max_output: usize,
+    overflowed: bool,
+}
+
+impl Io for Captured {
+    fn read(&mut self) -> Result<Option<u8>, IoError> {
+        let byte = self.input.get(self.read).copied();
+        self.read += usize::from(byte.is_some());
+        Ok(byte)
+    }
+    fn write(&mut self, byte: u8) -> Result<(), IoError> {
+        if self.output.len() >= self.max_output {
+            self.overflowed = true;
+            return Err(IoError::other("output limit reached"));
+        }
+        self.output.push(byte);
+        Ok(())
+    }
+}
+
+/// Parses and runs untrusted Brainfuck code on an input, stopping it once it
+/// uses more than `limits` allows.
+///
+/// The only optimization applied is removing code which can never run.
+/// The rest is executed one step at a time by an [`Interpreter`], as
+/// [`Optimized`](crate::Optimized) programs do many steps at once and
+/// check none of these limits, so `steps` counts the operations of the
+/// source. The time is kept by another thread, which cancels the program
+/// once it runs out.
+pub fn run(src: &str, input: impl AsRef<[u8]>, limits: SandboxLimits) -> SandboxResult {
+    let program = match Program::parse(src) {
+        Ok(program) => program.eliminate_dead_code().0,
+        Err(e) => {
+            return SandboxResult {
+                output: vec![],
+                steps: 0,
+                elapsed: Duration::ZERO,
+                result: Err(SandboxError::Parse(e)),
+            }
+        }
+    };
+    let io = Captured {
+        input: input.as_ref().to_vec(),
+        read: 0,
+        output: vec![],
+        max_output: limits.max_output,
+        overflowed: false,
+    };
+    let mut interpreter = Interpreter::builder()
+        .io(io)
+        .max_steps(limits.max_steps)
+        .max_memory(limits.max_memory)
+        .build(program);
+    let token = interpreter.cancel_token();
+    let (done, finished) = mpsc::channel::<()>();
+    let timer = thread::spawn(move || {
+        // Finishing drops the sender, which wakes the timer early.
+        if finished.recv_timeout(limits.timeout) == Err(mpsc::RecvTimeoutError::Timeout) {
+            token.cancel();
+        }
+    });
+    let start = Instant::now();
+    let result = interpreter.run();
+    let elapsed = start.elapsed();
+    drop(done);
+    timer.join().expect("the timer never panics");
+    let steps = interpreter.steps();
+    let (_, io) = interpreter.into_parts();
+    let result = result.map_err(|e| match e {
+        RuntimeError::Cancelled => SandboxError::Timeout(limits.timeout),
+        RuntimeError::Io(_) if io.overflowed => SandboxError::OutputLimit(limits.max_output),
+        e => SandboxError::Runtime(e),
+    });
+    SandboxResult {
+        output: io.output,
+        steps,
+        elapsed,
+        result,
+    }
+}
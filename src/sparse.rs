@@ -0,0 +1,402 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::mem;
+
+use crate::{Config, Eof, Io, Memory, Op, Program, RuntimeError, State};
+
+/// The number of cells allocated at once by a [`SparseTape`].
+pub const PAGE_SIZE: usize = 4096;
+
+/// Memory which only holds the pages of cells a program has written to, so
+/// programs using cells far apart do not need every cell between them.
+///
+/// Like a [`State`], it may have several tapes, of which the selected one's
+/// pages and pointer are used while the others are kept aside.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SparseTape {
+    pages: Pages,
+    /// The location of the pointer.
+    pub pointer: usize,
+    /// The number of bytes the program has written.
+    pub written: usize,
+    /// The state of the generator `?` reads pseudo-random bytes from.
+    pub rng: u64,
+    banks: Vec<(Pages, usize)>,
+    bank: usize,
+}
+
+/// The pages of one tape, by their index.
+type Pages = BTreeMap<usize, Box<[u8; PAGE_SIZE]>>;
+
+impl SparseTape {
+    /// Creates a tape with every cell zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds tapes, with every cell zero, until there are at least `tapes`.
+    pub fn ensure_tapes(&mut self, tapes: usize) {
+        if tapes <= self.tapes() {
+            return;
+        }
+        if self.banks.is_empty() {
+            self.banks.push(Default::default());
+        }
+        self.banks.resize_with(tapes, Default::default);
+    }
+
+    /// The number of tapes.
+    pub fn tapes(&self) -> usize {
+        self.banks.len().max(1)
+    }
+
+    /// The index of the selected tape.
+    pub fn bank(&self) -> usize {
+        self.bank
+    }
+
+    /// Selects a tape, wrapping around past the last one.
+    pub fn select_tape(&mut self, tape: usize) {
+        let tape = tape % self.tapes();
+        if tape == self.bank {
+            return;
+        }
+        self.banks[self.bank] = (mem::take(&mut self.pages), self.pointer);
+        (self.pages, self.pointer) = mem::take(&mut self.banks[tape]);
+        self.bank = tape;
+    }
+
+    /// Selects the tape before the selected one, or the last one.
+    pub fn prev_tape(&mut self) {
+        self.select_tape(self.bank + self.tapes() - 1);
+    }
+
+    /// Selects the tape after the selected one, or the first one.
+    pub fn next_tape(&mut self) {
+        self.select_tape(self.bank + 1);
+    }
+
+    /// Creates a tape holding the cells and pointers of every tape of
+    /// `state`, along with the number of bytes it has written and its
+    /// generator.
+    pub fn from_state(state: &State) -> Self {
+        let mut tape = Self::new();
+        tape.ensure_tapes(state.tapes());
+        for bank in 0..state.tapes() {
+            let (mem, pointer) = match bank == state.bank {
+                true => (&state.mem, state.pointer),
+                false => (&state.banks[bank].0, state.banks[bank].1),
+            };
+            tape.select_tape(bank);
+            for (index, &cell) in mem.iter().enumerate() {
+                if cell != 0 {
+                    tape.pointer = index;
+                    *tape.cell_mut() = cell;
+                }
+            }
+            tape.pointer = pointer;
+        }
+        tape.select_tape(state.bank);
+        tape.written = state.written;
+        tape.rng = state.rng;
+        tape
+    }
+
+    /// Writes the cells and pointer of every tape, the number of bytes
+    /// written, and the generator back into `state`, growing each memory as
+    /// far as its last non-zero cell and its pointer.
+    pub fn write_to(&self, state: &mut State) {
+        state.ensure_tapes(self.tapes());
+        state.select_tape(self.bank);
+        for bank in 0..self.tapes() {
+            let (pages, pointer) = match bank == self.bank {
+                true => (&self.pages, self.pointer),
+                false => (&self.banks[bank].0, self.banks[bank].1),
+            };
+            let (mem, at) = match bank == state.bank {
+                true => (&mut state.mem, &mut state.pointer),
+                false => {
+                    let (mem, at) = &mut state.banks[bank];
+                    (mem, at)
+                }
+            };
+            dense(pages, pointer, mem);
+            *at = pointer;
+        }
+        state.outted |= self.written > state.written;
+        state.written = self.written;
+        state.rng = self.rng;
+    }
+
+    /// The value of the cell at `index`.
+    pub fn get(&self, index: usize) -> u8 {
+        self.pages
+            .get(&(index / PAGE_SIZE))
+            .map_or(0, |page| page[index % PAGE_SIZE])
+    }
+
+    /// The value of the selected cell.
+    pub fn cell(&self) -> u8 {
+        self.get(self.pointer)
+    }
+
+    /// The selected cell, allocating its page if needed.
+    pub fn cell_mut(&mut self) -> &mut u8 {
+        let page = self
+            .pages
+            .entry(self.pointer / PAGE_SIZE)
+            .or_insert_with(|| Box::new([0; PAGE_SIZE]));
+        &mut page[self.pointer % PAGE_SIZE]
+    }
+
+    /// The number of pages allocated, on every tape.
+    pub fn pages(&self) -> usize {
+        self.pages.len()
+            + self
+                .banks
+                .iter()
+                .map(|(pages, _)| pages.len())
+                .sum::<usize>()
+    }
+
+    /// The number of cells allocated on every tape, in whole pages.
+    pub fn cells(&self) -> usize {
+        self.pages() * PAGE_SIZE
+    }
+
+    /// Every non-zero cell of the selected tape along with its location, in
+    /// order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, u8)> + '_ {
+        cells(&self.pages)
+    }
+}
+
+/// Every non-zero cell of some pages along with its location, in order.
+fn cells(pages: &Pages) -> impl Iterator<Item = (usize, u8)> + '_ {
+    pages.iter().flat_map(|(&page, cells)| {
+        cells
+            .iter()
+            .enumerate()
+            .filter(|&(_, &cell)| cell != 0)
+            .map(move |(i, &cell)| (page * PAGE_SIZE + i, cell))
+    })
+}
+
+/// Writes the cells of some pages over a dense memory, growing it as far as
+/// the last non-zero cell and the pointer.
+fn dense(pages: &Pages, pointer: usize, mem: &mut Memory) {
+    let last = cells(pages).last().map_or(0, |(index, _)| index + 1);
+    mem.fill(0);
+    mem.resize(mem.len().max(last).max(pointer + 1), 0);
+    for (index, cell) in cells(pages) {
+        mem[index] = cell;
+    }
+}
+
+/// Runs a program to completion on a sparse tape, reading from and writing
+/// to `io`, returning the number of steps it took.
+///
+/// The limits, strict mode, end of input behavior, and number of tapes of
+/// `config` apply, as they do on a dense tape. `?` carries on from the
+/// tape's generator, [`SparseTape::rng`], rather than the seed.
+pub fn run(
+    program: &Program,
+    tape: &mut SparseTape,
+    io: &mut impl Io,
+    config: &Config,
+) -> Result<u64, RuntimeError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("run_sparse", ops = program.len()).entered();
+    let ops = program.ops();
+    let (mut position, mut steps) = (0, 0);
+    tape.ensure_tapes(config.tapes);
+    while let Some(&op) = ops.get(position) {
+        if let Some(max_steps) = config.max_steps {
+            if steps >= max_steps {
+                return Err(RuntimeError::StepLimit(max_steps, None));
+            }
+        }
+        match op {
+            Op::Increment => {
+                if config.strict && tape.cell() == u8::MAX {
+                    return Err(RuntimeError::CellOverflow);
+                }
+                let cell = tape.cell_mut();
+                *cell = cell.wrapping_add(1);
+            }
+            Op::Decrement => {
+                if config.strict && tape.cell() == 0 {
+                    return Err(RuntimeError::CellOverflow);
+                }
+                let cell = tape.cell_mut();
+                *cell = cell.wrapping_sub(1);
+            }
+            Op::Forward => {
+                if let Some(max_memory) = config.max_memory {
+                    if tape.pointer + 1 >= max_memory {
+                        return Err(RuntimeError::MemoryLimit(max_memory));
+                    }
+                }
+                tape.pointer += 1
+            }
+            Op::Backward => {
+                if config.strict && tape.pointer == 0 {
+                    return Err(RuntimeError::PointerUnderflow);
+                }
+                tape.pointer = tape.pointer.saturating_sub(1);
+            }
+            Op::Open(close) => {
+                if tape.cell() == 0 {
+                    position = close;
+                }
+            }
+            Op::Close(open) => {
+                if tape.cell() != 0 {
+                    position = open;
+                }
+            }
+            Op::Out => {
+                io.write(tape.cell())?;
+                tape.written += 1;
+            }
+            Op::In => match io.read()? {
+                Some(b) => *tape.cell_mut() = b,
+                None if config.eof != Eof::Unchanged => {
+                    let cell = tape.cell_mut();
+                    *cell = config.eof.value(*cell);
+                }
+                None => {}
+            },
+            Op::PrevTape => tape.prev_tape(),
+            Op::NextTape => tape.next_tape(),
+            Op::Random => *tape.cell_mut() = crate::random(&mut tape.rng),
+        }
+        position += 1;
+        steps += 1;
+    }
+    io.flush()?;
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Buffer, Interpreter};
+
+    /// Programs along with the input they are given and the number of tapes
+    /// they use.
+    const PROGRAMS: &[(&str, &str, usize)] = &[
+        ("++++++++[>++++++++<-]>+.>>>>++[<+>-]<.", "", 1),
+        (",[.[-],]", "echo", 1),
+        ("+}++}+++{.{.{.", "", 3),
+        ("?.>?.", "", 1),
+        ("+[>+]", "", 1),
+    ];
+
+    fn configs(tapes: usize) -> [Config; 3] {
+        let config = Config {
+            tapes,
+            random: Some(7),
+            max_steps: Some(100_000),
+            ..Config::default()
+        };
+        [
+            config,
+            Config {
+                max_memory: Some(300),
+                ..config
+            },
+            Config {
+                strict: true,
+                eof: Eof::Zero,
+                ..config
+            },
+        ]
+    }
+
+    #[test]
+    fn runs_like_a_dense_tape() {
+        for &(code, input, tapes) in PROGRAMS {
+            for config in configs(tapes) {
+                let program = Program::parse_with(code, &config).unwrap();
+                let mut interpreter = Interpreter::builder()
+                    .io(Buffer::new(input.as_bytes().to_vec()))
+                    .config(&config)
+                    .build(program.clone());
+                // Only the dense tape says which loop is spinning.
+                let dense = interpreter.run().map_err(|e| mem::discriminant(&e));
+                let (dense_state, dense_io) = interpreter.into_parts();
+
+                let mut tape = SparseTape::new();
+                tape.rng = 7;
+                let mut io = Buffer::new(input.as_bytes().to_vec());
+                let sparse = run(&program, &mut tape, &mut io, &config);
+                let sparse = sparse.map(drop).map_err(|e| mem::discriminant(&e));
+                assert_eq!(sparse, dense, "{code}");
+                assert_eq!(io.output, dense_io.output, "{code}");
+
+                let mut state = State::new();
+                tape.write_to(&mut state);
+                for bank in 0..tapes {
+                    state.select_tape(bank);
+                    let mut dense_state = dense_state.clone();
+                    dense_state.select_tape(bank);
+                    let trimmed = |mem: &[u8]| {
+                        let len = mem.iter().rposition(|&cell| cell != 0).map_or(0, |i| i + 1);
+                        mem[..len].to_vec()
+                    };
+                    assert_eq!(trimmed(&state.mem), trimmed(&dense_state.mem), "{code}");
+                    assert_eq!(state.pointer, dense_state.pointer, "{code}");
+                }
+                assert_eq!(state.rng, dense_state.rng, "{code}");
+            }
+        }
+    }
+
+    #[test]
+    fn only_allocates_the_pages_written_to() {
+        let mut tape = SparseTape::new();
+        tape.pointer = 10 * PAGE_SIZE;
+        *tape.cell_mut() = 1;
+        tape.pointer = 3;
+        *tape.cell_mut() = 2;
+        assert_eq!(tape.pages(), 2);
+        assert_eq!(
+            tape.iter().collect::<Vec<_>>(),
+            [(3, 2), (10 * PAGE_SIZE, 1)]
+        );
+        assert_eq!(tape.get(PAGE_SIZE), 0);
+        assert_eq!(tape.pages(), 2);
+    }
+
+    #[test]
+    fn keeps_every_tape_of_a_state() {
+        let mut state = State::with_tapes(2);
+        state.mem = vec![0, 5, 0, 6];
+        state.pointer = 2;
+        state.select_tape(1);
+        state.mem = vec![7];
+        state.rng = 42;
+        let tape = SparseTape::from_state(&state);
+        assert_eq!((tape.tapes(), tape.bank(), tape.rng), (2, 1, 42));
+        let mut copy = State::new();
+        tape.write_to(&mut copy);
+        assert_eq!(
+            (copy.bank, copy.mem.as_slice(), copy.rng),
+            (1, &[7][..], 42)
+        );
+        copy.select_tape(0);
+        assert_eq!((copy.mem.as_slice(), copy.pointer), (&[0, 5, 0, 6][..], 2));
+    }
+
+    #[test]
+    fn carries_on_from_its_generator() {
+        let program = Program::parse_with("?.", &configs(1)[0]).unwrap();
+        let mut tape = SparseTape::new();
+        let mut io = Buffer::default();
+        run(&program, &mut tape, &mut io, &Config::default()).unwrap();
+        run(&program, &mut tape, &mut io, &Config::default()).unwrap();
+        assert_ne!(io.output[0], io.output[1]);
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt;
+
+use crate::{Buffer, Interpreter, ParseError, Program, RuntimeError, Spin, State};
+
+/// The step limit applied by [`TestCase`] unless another is given.
+pub const DEFAULT_MAX_STEPS: u64 = 10_000_000;
+
+/// A Brainfuck program along with the input to give it and the output
+/// it is expected to produce.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCase {
+    /// The source code of the program.
+    pub source: String,
+    /// The bytes read by `,`.
+    pub input: Vec<u8>,
+    /// The bytes expected to be written by `.`, if checked.
+    pub output: Option<Vec<u8>>,
+    /// The number of steps after which the program is considered stuck.
+    pub max_steps: u64,
+}
+
+impl TestCase {
+    /// Creates a test case with no input, which only checks that the
+    /// program halts within [`DEFAULT_MAX_STEPS`].
+    pub fn new(source: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+            input: vec![],
+            output: None,
+            max_steps: DEFAULT_MAX_STEPS,
+        }
+    }
+
+    /// Sets the input given to the program.
+    pub fn input(mut self, input: impl AsRef<[u8]>) -> Self {
+        self.input = input.as_ref().to_vec();
+        self
+    }
+
+    /// Sets the output the program is expected to produce.
+    pub fn output(mut self, output: impl AsRef<[u8]>) -> Self {
+        self.output = Some(output.as_ref().to_vec());
+        self
+    }
+
+    /// Sets the number of steps after which the program is considered stuck.
+    pub fn max_steps(mut self, max_steps: u64) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Runs the program, returning its final state and output if it
+    /// behaved as expected.
+    pub fn run(&self) -> Result<(State, Vec<u8>), Failure> {
+        let program = Program::parse(&self.source).map_err(Failure::Parse)?;
+        let io = Buffer::new(self.input.clone());
+        let mut interpreter = Interpreter::with_io(program, State::new(), io);
+        interpreter.limit_steps(self.max_steps);
+        let result = interpreter.run();
+        let (state, io) = interpreter.into_parts();
+        match result {
+            Ok(()) => {}
+            Err(RuntimeError::StepLimit(max_steps, spin)) => {
+                return Err(Failure::Stuck {
+                    max_steps,
+                    spin,
+                    output: io.output,
+                })
+            }
+            Err(e) => return Err(Failure::Runtime(e)),
+        }
+        match &self.output {
+            Some(expected) if *expected != io.output => Err(Failure::Output {
+                expected: expected.clone(),
+                actual: io.output,
+            }),
+            _ => Ok((state, io.output)),
+        }
+    }
+}
+
+/// Why a [`TestCase`] failed.
+#[derive(Debug)]
+pub enum Failure {
+    /// The program could not be parsed.
+    Parse(ParseError),
+    /// The program failed while running.
+    Runtime(RuntimeError),
+    /// The program did not halt within the step limit.
+    Stuck {
+        /// The step limit that was reached.
+        max_steps: u64,
+        /// The loop the program was running when it reached the limit.
+        spin: Option<Spin>,
+        /// The output produced before the limit was reached.
+        output: Vec<u8>,
+    },
+    /// The program halted with the wrong output.
+    Output {
+        /// The output that was expected.
+        expected: Vec<u8>,
+        /// The output that was produced.
+        actual: Vec<u8>,
+    },
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Parse(_) => write!(f, "Could not parse the program"),
+            Failure::Runtime(_) => write!(f, "The program failed"),
+            Failure::Stuck {
+                max_steps,
+                spin,
+                output,
+            } => {
+                write!(f, "The program did not halt within {max_steps} steps")?;
+                if let Some(spin) = spin {
+                    write!(f, ", running {spin},")?;
+                }
+                write!(f, " having output {:?}.", String::from_utf8_lossy(output))
+            }
+            Failure::Output { expected, actual } => write!(
+                f,
+                "Expected the program to output {:?}, but it output {:?}.",
+                String::from_utf8_lossy(expected),
+                String::from_utf8_lossy(actual)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Failure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Failure::Parse(e) => Some(e),
+            Failure::Runtime(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Asserts that a Brainfuck program halts, optionally checking its output.
+///
+/// Used as `assert_bf!(source)`, `assert_bf!(source, output: "xyz")` or
+/// `assert_bf!(source, input: "abc", output: "xyz")`. Panics with the reason
+/// if the program cannot be parsed, does not halt within
+/// [`DEFAULT_MAX_STEPS`], or produces the wrong output.
+#[macro_export]
+macro_rules! assert_bf {
+    (@run $case:expr) => {
+        if let ::std::result::Result::Err(failure) = $case.run() {
+            ::std::panic!("{}", $crate::error::Chain(&failure));
+        }
+    };
+    ($source:expr $(,)?) => {
+        $crate::assert_bf!(@run $crate::testing::TestCase::new($source))
+    };
+    ($source:expr, output: $output:expr $(,)?) => {
+        $crate::assert_bf!(@run $crate::testing::TestCase::new($source).output($output))
+    };
+    ($source:expr, input: $input:expr, output: $output:expr $(,)?) => {
+        $crate::assert_bf!(
+            @run $crate::testing::TestCase::new($source).input($input).output($output)
+        )
+    };
+}
@@ -0,0 +1,255 @@
+use alloc::string::String;
+use alloc::vec;
+use core::fmt::Write;
+
+use crate::optimize::{Ir, Mapping, Optimized, SourceMap};
+use crate::{Config, Eof};
+
+/// The code every C program starts with: the memory, which grows as the
+/// pointer moves right, and the helpers the operations are written with.
+const PRELUDE: &str = r#"#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+static unsigned char *mem;
+static size_t len, ptr;
+
+static void fail(const char *message) {
+    fflush(stdout);
+    fprintf(stderr, "%s\n", message);
+    exit(1);
+}
+
+/* The cell at an offset from the pointer, growing the memory to reach it. */
+static unsigned char *at(ptrdiff_t offset) {
+    size_t index;
+    if (offset < 0 && (size_t)-offset > ptr)
+        fail("`<` was run on the first cell.");
+    index = ptr + offset;
+    if (index >= len) {
+        size_t grown = index + 1 > len * 2 ? index + 1 : len * 2;
+        mem = realloc(mem, grown);
+        if (!mem)
+            fail("Out of memory.");
+        memset(mem + len, 0, grown - len);
+        len = grown;
+    }
+    return &mem[index];
+}
+"#;
+
+/// Transpiles an optimized program to C, along with a map from each line
+/// of the C code to the source it came from.
+///
+/// Each operation is written on a line of its own, so a line a C compiler,
+/// debugger, or sanitizer reports can be looked up in the map. The end of
+/// input behavior, number of tapes, and seed of `config` are written into
+/// the code. Like running the optimized program, the C program fails when
+/// it would move left of the first cell while clearing, copying, or
+/// scanning.
+pub fn to_c(optimized: &Optimized, config: &Config) -> (String, SourceMap) {
+    let uses = |f: fn(&Ir) -> bool| optimized.ir().iter().any(f);
+    let mut code = String::from(PRELUDE);
+    if uses(|ir| matches!(ir, Ir::Move(_) | Ir::Sweep(_))) {
+        code.push_str(
+            "
+/* Moves the pointer, stopping at the first cell like `<` does. */
+static void move(ptrdiff_t by) {
+    ptr = by < 0 && (size_t)-by > ptr ? 0 : ptr + by;
+    at(0);
+}
+",
+        );
+    }
+    if uses(|ir| matches!(ir, Ir::Scan(_))) {
+        code.push_str(
+            "
+/* Moves the pointer, failing if it would move left of the first cell. */
+static void step(ptrdiff_t by) {
+    at(by);
+    ptr += by;
+}
+",
+        );
+    }
+    if uses(|ir| matches!(ir, Ir::Fill { .. })) {
+        code.push_str(
+            "
+static void fill(ptrdiff_t from, size_t n) {
+    at(from);
+    at(from + (ptrdiff_t)n - 1);
+    memset(&mem[ptr + from], 0, n);
+}
+",
+        );
+    }
+    if uses(|ir| matches!(ir, Ir::Load { .. })) {
+        code.push_str(
+            "
+static void load(const unsigned char *cells, size_t n, size_t pointer) {
+    ptr = 0;
+    at(n - 1);
+    memcpy(mem, cells, n);
+    ptr = pointer;
+    at(0);
+}
+",
+        );
+    }
+    if uses(|ir| matches!(ir, Ir::In)) {
+        let eof = match config.eof {
+            Eof::Unchanged => "",
+            Eof::Zero => " else *at(0) = 0;",
+            Eof::Max => " else *at(0) = 255;",
+        };
+        let _ = write!(
+            code,
+            "
+static void input(void) {{
+    int c = getchar();
+    if (c != EOF) *at(0) = c;{eof}
+}}
+"
+        );
+    }
+    if uses(|ir| matches!(ir, Ir::PrevTape | Ir::NextTape)) {
+        let _ = write!(
+            code,
+            "
+#define TAPES {}
+static struct tape {{ unsigned char *mem; size_t len, ptr; }} banks[TAPES];
+static size_t bank;
+
+static void select_tape(size_t next) {{
+    banks[bank].mem = mem;
+    banks[bank].len = len;
+    banks[bank].ptr = ptr;
+    bank = next % TAPES;
+    mem = banks[bank].mem;
+    len = banks[bank].len;
+    ptr = banks[bank].ptr;
+    at(0);
+}}
+",
+            config.tapes.max(1)
+        );
+    }
+    if uses(|ir| matches!(ir, Ir::Random)) {
+        let _ = write!(
+            code,
+            "
+static uint64_t rng = {}u;
+
+static unsigned char random_byte(void) {{
+    uint64_t z = rng += 0x9E3779B97F4A7C15u;
+    z = (z ^ (z >> 30)) * 0xBF58476D1CE4E5B9u;
+    z = (z ^ (z >> 27)) * 0x94D049BB133111EBu;
+    return (z ^ (z >> 31)) >> 56;
+}}
+",
+            config.random.unwrap_or_default()
+        );
+    }
+    code.push_str("\nint main(void) {\n    at(0);\n");
+
+    let mut map = SourceMap(vec![]);
+    let first = code.matches('\n').count() + 1;
+    let mut depth = 1;
+    let lines = optimized.ir().iter().zip(optimized.spans());
+    for (line, (ir, &span)) in (first..).zip(lines) {
+        if let Ir::Close(_) = ir {
+            depth -= 1;
+        }
+        code.extend(core::iter::repeat_n("    ", depth));
+        let _ = match ir {
+            Ir::Add(value) => write!(code, "*at(0) += {value};"),
+            Ir::Move(by) => write!(code, "move({by});"),
+            Ir::Clear => write!(code, "*at(0) = 0;"),
+            Ir::MulAdd(targets) => {
+                code.push_str("if (*at(0)) { unsigned char v = *at(0);");
+                for (offset, factor) in targets {
+                    let _ = write!(code, " *at({offset}) += v * {factor};");
+                }
+                write!(code, " *at(0) = 0; }}")
+            }
+            Ir::Update(targets) => {
+                for (i, (offset, value)) in targets.iter().enumerate() {
+                    let space = if i == 0 { "" } else { " " };
+                    let _ = write!(code, "{space}*at({offset}) += {value};");
+                }
+                Ok(())
+            }
+            Ir::Scan(stride) => write!(code, "while (*at(0)) step({stride});"),
+            Ir::Sweep(stride) => write!(code, "while (*at(0)) {{ *at(0) = 0; move({stride}); }}"),
+            Ir::Fill { from, len } => write!(code, "fill({from}, {len});"),
+            Ir::Open(_) => write!(code, "while (*at(0)) {{"),
+            Ir::Close(_) => write!(code, "}}"),
+            Ir::Load { cells, pointer } => {
+                code.push_str("{ static const unsigned char cells[] = {");
+                for (i, cell) in cells.iter().enumerate() {
+                    let comma = if i == 0 { "" } else { ", " };
+                    let _ = write!(code, "{comma}{cell}");
+                }
+                write!(code, "}}; load(cells, sizeof cells, {pointer}); }}")
+            }
+            Ir::Write(bytes) => {
+                code.push_str("fwrite(\"");
+                for b in bytes {
+                    let _ = write!(code, "\\x{b:02x}");
+                }
+                write!(code, "\", 1, {}, stdout);", bytes.len())
+            }
+            Ir::Out => write!(code, "putchar(*at(0));"),
+            Ir::In => write!(code, "input();"),
+            Ir::PrevTape => write!(code, "select_tape(bank + TAPES - 1);"),
+            Ir::NextTape => write!(code, "select_tape(bank + 1);"),
+            Ir::Random => write!(code, "*at(0) = random_byte();"),
+        };
+        code.push('\n');
+        if let Ir::Open(_) = ir {
+            depth += 1;
+        }
+        map.0.push(Mapping {
+            generated: line..line + 1,
+            original: span,
+        });
+    }
+    code.push_str("    fflush(stdout);\n    return 0;\n}\n");
+    (code, map)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::Program;
+
+    #[test]
+    fn maps_each_line_to_its_operation() {
+        let source = ",[\n->+<\n]>.";
+        let optimized = Program::parse(source).unwrap().optimize();
+        let (code, map) = to_c(&optimized, &Config::default());
+        let lines: Vec<&str> = code.lines().collect();
+        let line = |span: &str| {
+            let start = source.find(span).unwrap();
+            let mapping = map
+                .mappings()
+                .iter()
+                .find(|m| m.original.start == start)
+                .unwrap();
+            assert_eq!(mapping.generated.len(), 1);
+            lines[mapping.generated.start - 1].trim()
+        };
+        assert_eq!(line(","), "input();");
+        assert_eq!(
+            line("["),
+            "if (*at(0)) { unsigned char v = *at(0); *at(1) += v * 1; *at(0) = 0; }"
+        );
+        assert_eq!(line(">."), "move(1);");
+        assert_eq!(line("."), "putchar(*at(0));");
+        assert_eq!(map.lookup(1), None);
+    }
+}
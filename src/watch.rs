@@ -0,0 +1,259 @@
+use std::time::Duration;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span as TextSpan, Text};
+use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::{Buffer, Chain, Encoding, Error, Interpreter, Op, Program, RuntimeError, State};
+
+/// The number of columns each cell of the tape takes up.
+const CELL_WIDTH: usize = 5;
+
+/// Runs a program in a terminal interface showing its source, tape, and
+/// output as it executes.
+///
+/// The program starts paused. Space executes one instruction, Enter runs or
+/// pauses it, `+` and `-` change the speed, the arrow keys scroll the tape,
+/// and `q` quits. When the program reads input, the next key pressed is
+/// given to it, or Ctrl-D for the end of input.
+pub fn watch<E: Encoding>(program: Program, encoding: E) -> Result<(), Error> {
+    let mut terminal = ratatui::init();
+    let result = Watch::new(program, encoding).run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+/// The progress of a program being watched.
+struct Watch<E> {
+    interpreter: Interpreter<Buffer>,
+    encoding: E,
+    running: bool,
+    delay: Duration,
+    /// The first cell shown on the tape.
+    offset: usize,
+    /// How many cells fit on the tape.
+    visible: usize,
+    /// Whether the end of input has been given.
+    eof: bool,
+    error: Option<RuntimeError>,
+    quit: bool,
+}
+
+impl<E: Encoding> Watch<E> {
+    fn new(program: Program, encoding: E) -> Self {
+        Self {
+            interpreter: Interpreter::with_io(program, State::new(), Buffer::default()),
+            encoding,
+            running: false,
+            delay: Duration::from_millis(100),
+            offset: 0,
+            visible: 1,
+            eof: false,
+            error: None,
+            quit: false,
+        }
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), Error> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            let paused = !self.running || self.is_waiting();
+            if paused || event::poll(self.delay)? {
+                self.handle(event::read()?);
+            } else {
+                self.step();
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the program is waiting for a key to read.
+    fn is_waiting(&self) -> bool {
+        let ops = self.interpreter.program().ops();
+        ops.get(self.interpreter.position()) == Some(&Op::In)
+            && self.interpreter.state().queued.is_empty()
+            && self.interpreter.io().input.is_empty()
+            && !self.eof
+    }
+
+    fn step(&mut self) {
+        if self.interpreter.is_halted() || self.is_waiting() || self.error.is_some() {
+            self.running = false;
+            return;
+        }
+        if let Err(e) = self.interpreter.step() {
+            self.error = Some(e);
+            self.running = false;
+        }
+        let pointer = self.interpreter.state().pointer;
+        if pointer < self.offset || pointer >= self.offset + self.visible {
+            self.offset = pointer.saturating_sub(self.visible / 2);
+        }
+    }
+
+    fn handle(&mut self, event: Event) {
+        let Event::Key(key) = event else {
+            return;
+        };
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        let interrupt =
+            key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c');
+        if interrupt || key.code == KeyCode::Esc {
+            self.quit = true;
+        } else if self.is_waiting() {
+            self.input(key);
+        } else {
+            match key.code {
+                KeyCode::Char('q') => self.quit = true,
+                KeyCode::Char(' ') => {
+                    self.running = false;
+                    self.step();
+                }
+                KeyCode::Enter => self.running = !self.running,
+                KeyCode::Char('+') => self.delay = (self.delay / 2).max(Duration::from_millis(1)),
+                KeyCode::Char('-') => self.delay = (self.delay * 2).min(Duration::from_secs(2)),
+                KeyCode::Left => self.offset = self.offset.saturating_sub(1),
+                KeyCode::Right => self.offset += 1,
+                _ => {}
+            }
+        }
+    }
+
+    /// Gives a key to a program waiting for input.
+    fn input(&mut self, key: KeyEvent) {
+        let c = match key.code {
+            KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.eof = true;
+                return;
+            }
+            KeyCode::Char(c) => c,
+            KeyCode::Enter => '\n',
+            KeyCode::Tab => '\t',
+            _ => return,
+        };
+        if let Some(b) = self.encoding.encode(c) {
+            self.interpreter.io_mut().input.push_back(b);
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [source, tape, output, status] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(4),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        self.visible = (tape.width.saturating_sub(2) as usize / CELL_WIDTH).max(1);
+
+        self.draw_source(frame, source);
+        self.draw_tape(frame, tape);
+        self.draw_output(frame, output);
+        frame.render_widget(Paragraph::new(self.status()), status);
+    }
+
+    /// Draws the source, highlighting the instruction about to be executed.
+    fn draw_source(&self, frame: &mut Frame, area: Rect) {
+        let program = self.interpreter.program();
+        let source = program.source();
+        let current = program.spans().get(self.interpreter.position());
+        let highlight = Style::new().add_modifier(Modifier::REVERSED);
+
+        let mut lines = vec![];
+        let mut start = 0;
+        for line in source.split('\n') {
+            let text = line.strip_suffix('\r').unwrap_or(line);
+            let end = start + text.len();
+            lines.push(match current {
+                Some(span) if span.start >= start && span.start < end => Line::from(vec![
+                    TextSpan::raw(&source[start..span.start]),
+                    TextSpan::styled(&source[span.start..span.end], highlight),
+                    TextSpan::raw(&source[span.end..end]),
+                ]),
+                _ => Line::raw(text),
+            });
+            start += line.len() + 1;
+        }
+
+        let line = current.map_or(0, |span| span.line - 1);
+        let scroll = line.saturating_sub(area.height.saturating_sub(2) as usize / 2);
+        let paragraph = Paragraph::new(Text::from(lines))
+            .block(Block::new().borders(Borders::ALL).title("Source"))
+            .scroll((scroll as u16, 0));
+        frame.render_widget(paragraph, area);
+    }
+
+    /// Draws the visible cells of the tape, highlighting the pointer.
+    fn draw_tape(&self, frame: &mut Frame, area: Rect) {
+        let state = self.interpreter.state();
+        let highlight = Style::new().add_modifier(Modifier::REVERSED);
+
+        let mut indices = vec![];
+        let mut values = vec![];
+        for index in self.offset..self.offset + self.visible {
+            let value = state.mem.get(index).copied().unwrap_or(0);
+            let style = if index == state.pointer {
+                highlight
+            } else {
+                Style::new()
+            };
+            indices.push(TextSpan::styled(
+                format!("{index:^width$}", width = CELL_WIDTH),
+                style.add_modifier(Modifier::DIM),
+            ));
+            values.push(TextSpan::styled(
+                format!("{value:^width$}", width = CELL_WIDTH),
+                style,
+            ));
+        }
+
+        let title = format!("Tape (pointer at {})", state.pointer);
+        let paragraph = Paragraph::new(vec![Line::from(indices), Line::from(values)])
+            .block(Block::new().borders(Borders::ALL).title(title));
+        frame.render_widget(paragraph, area);
+    }
+
+    /// Draws the end of the output written so far.
+    fn draw_output(&self, frame: &mut Frame, area: Rect) {
+        let output: String = self
+            .interpreter
+            .io()
+            .output
+            .iter()
+            .map(|&b| match self.encoding.decode(b) {
+                c if c.is_control() && c != '\n' => ' ',
+                c => c,
+            })
+            .collect();
+        let lines: Vec<&str> = output.split('\n').collect();
+        let height = area.height.saturating_sub(2) as usize;
+        let shown = lines[lines.len().saturating_sub(height)..].join("\n");
+        let paragraph =
+            Paragraph::new(shown).block(Block::new().borders(Borders::ALL).title("Output"));
+        frame.render_widget(paragraph, area);
+    }
+
+    fn status(&self) -> String {
+        let progress = if let Some(e) = &self.error {
+            Chain(e).to_string()
+        } else if self.interpreter.is_halted() {
+            "Halted.".to_string()
+        } else if self.is_waiting() {
+            "Waiting for input, press a key or Ctrl-D.".to_string()
+        } else if self.running {
+            "Running.".to_string()
+        } else {
+            "Paused.".to_string()
+        };
+        format!(
+            "{progress} Steps: {}, delay: {:?}. [space] step [enter] run/pause [+/-] speed [←/→] scroll [q] quit",
+            self.interpreter.steps(),
+            self.delay,
+        )
+    }
+}